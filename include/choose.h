@@ -0,0 +1,63 @@
+#ifndef CHOOSE_H
+#define CHOOSE_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Compiled choices and separator, opaque to C callers
+ */
+typedef struct ChooseSelector ChooseSelector;
+
+/**
+ * Byte offsets of a selected field within the buffer it was selected from
+ */
+typedef struct ChooseField {
+  size_t start;
+  size_t end;
+} ChooseField;
+
+/**
+ * Compile `choices`, whitespace separated choices written as on the command line, and the
+ * field `separator` regex into a selector. A null `separator` splits on whitespace.
+ *
+ * Returns null if either string is not valid UTF-8, a choice does not parse or the separator
+ * is not a valid regex. The selector must be released with `choose_selector_free`.
+ *
+ * # Safety
+ *
+ * `choices` must be a valid NUL-terminated string and `separator` either null or a valid
+ * NUL-terminated string.
+ */
+struct ChooseSelector *choose_selector_new(const char *choices, const char *separator);
+
+/**
+ * Select fields from the `len` bytes of UTF-8 text at `buf`, writing the offsets of at most
+ * `capacity` of them to `fields` in the order the choices pick them.
+ *
+ * Returns the number of fields selected, which may be more than `capacity` so the caller can
+ * retry with a larger array, or -1 if an argument is null or `buf` is not valid UTF-8.
+ *
+ * # Safety
+ *
+ * `selector` must come from `choose_selector_new` and not be used by another thread during the
+ * call, `buf` must point to `len` readable bytes and `fields` to `capacity` writable fields.
+ */
+ptrdiff_t choose_selector_apply(struct ChooseSelector *selector,
+                                const uint8_t *buf,
+                                size_t len,
+                                struct ChooseField *fields,
+                                size_t capacity);
+
+/**
+ * Release a selector. Null is ignored.
+ *
+ * # Safety
+ *
+ * `selector` must be null or come from `choose_selector_new`, and not be used afterwards.
+ */
+void choose_selector_free(struct ChooseSelector *selector);
+
+#endif  /* CHOOSE_H */
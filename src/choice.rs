@@ -1,35 +1,93 @@
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::convert::TryInto;
+use std::fmt;
+use std::io::Write;
+use std::iter::Rev;
+use std::num::{IntErrorKind, ParseIntError};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 
 use crate::config::Config;
-use crate::io::{BufWriter, Write};
+use crate::diagnostic::Diagnostic;
+use crate::fields::Fields;
+use crate::preset::PRESETS;
+use crate::shell::{self, QuoteStyle};
 
+lazy_static! {
+    static ref PARSE_CHOICE_RE: Regex = Regex::new(r"^(-?\d*):(-?\d*)$").unwrap();
+    static ref DASH_RANGE_RE: Regex = Regex::new(r"^(-?\d+)-(-?\d+)$").unwrap();
+    static ref DOT_RANGE_RE: Regex = Regex::new(r"^(-?\d*)\.\.(=?)(-?\d*)$").unwrap();
+    static ref AWK_FIELD_RE: Regex = Regex::new(r"^\$(\d+)$").unwrap();
+    static ref NAME_RE: Regex = Regex::new(r"^[[:alpha:]][[:alnum:]_-]*$").unwrap();
+}
+
+/// The choice syntax, for error messages
+pub const CHOICE_GRAMMAR: &str = "a choice is x, x:, :y or x:y, where x and y are integers, \
+                                  negative ones counting back from the end of the line";
+
+/// An inclusive range of fields to print.
+///
+/// Whether each bound counts from the end of the line is fixed when the choice is created, so
+/// `-x` can move an end of `0` to `-1` without it wrapping around to the last field.
 #[derive(Debug)]
 pub struct Choice {
-    pub start: isize,
-    pub end: isize,
-    negative_index: bool,
+    start: isize,
+    end: isize,
+    negative_start: bool,
+    negative_end: bool,
     reversed: bool,
 }
 
 impl Choice {
     pub fn new(start: isize, end: isize) -> Self {
-        let negative_index = start < 0 || end < 0;
+        let negative_start = start < 0;
+        let negative_end = end < 0;
         let reversed = end < start;
         Choice {
             start,
             end,
-            negative_index,
+            negative_start,
+            negative_end,
             reversed,
         }
     }
 
-    pub fn print_choice<WriterType: Write>(
+    /// The first field of the range, negative when counted back from the end of the line
+    pub fn start(&self) -> isize {
+        self.start
+    }
+
+    /// The last field of the range, negative when counted back from the end of the line
+    pub fn end(&self) -> isize {
+        self.end
+    }
+
+    /// Leave out the last field of the range, as -x does
+    pub(crate) fn exclude_end(&mut self) {
+        if self.runs_backwards() {
+            self.start = self.start.saturating_sub(1);
+        } else if self.end != isize::max_value() {
+            // an open end has no last field to leave out
+            self.end = self.end.saturating_sub(1);
+        }
+    }
+
+    pub(crate) fn print_choice<WriterType: Write>(
         &self,
-        line: &String,
+        line: &str,
         config: &Config,
-        handle: &mut BufWriter<WriterType>,
+        handle: &mut WriterType,
     ) {
-        let mut line_iter = config.separator.split(line).filter(|s| !s.is_empty());
+        if config.max_split.is_some() {
+            // the field holding the rest of the line ends with the line, so it can't be streamed
+            let mut fields = Fields::new();
+            fields.split(line, &config.tokenizer, None);
+            fields.limit_splits(config.max_split);
+            return self.print_fields(line, &fields, config, handle);
+        }
+        let mut line_iter = config.tokenizer.fields(line);
 
         if self.is_reverse_range() && !self.has_negative_index() {
             if self.end > 0 {
@@ -48,40 +106,13 @@ impl Choice {
                 }
             }
 
-            loop {
-                match stack.pop() {
-                    Some(s) => Choice::write_bytes(handle, s.as_bytes()),
-                    None => break,
-                }
+            while let Some(s) = stack.pop() {
+                Choice::write_field(handle, &s, config);
             }
+        } else if self.negative_end && !self.negative_start {
+            self.print_until_from_end(line_iter, config, handle);
         } else if self.has_negative_index() {
-            let vec = line_iter.collect::<Vec<&str>>();
-
-            let start = if self.start >= 0 {
-                self.start.try_into().unwrap()
-            } else {
-                vec.len()
-                    .checked_sub(self.start.abs().try_into().unwrap())
-                    .unwrap()
-            };
-
-            let end = if self.end >= 0 {
-                self.end.try_into().unwrap()
-            } else {
-                vec.len()
-                    .checked_sub(self.end.abs().try_into().unwrap())
-                    .unwrap()
-            };
-
-            if end > start {
-                for word in vec[start..=std::cmp::min(end, vec.len() - 1)].iter() {
-                    Choice::write_bytes(handle, word.as_bytes());
-                }
-            } else if self.start < 0 {
-                for word in vec[end..=std::cmp::min(start, vec.len() - 1)].iter().rev() {
-                    Choice::write_bytes(handle, word.as_bytes());
-                }
-            }
+            self.print_from_end(line_iter, config, handle);
         } else {
             if self.start > 0 {
                 line_iter.nth((self.start - 1).try_into().unwrap());
@@ -89,7 +120,7 @@ impl Choice {
 
             for i in 0..=(self.end - self.start) {
                 match line_iter.next() {
-                    Some(s) => Choice::write_bytes(handle, s.as_bytes()),
+                    Some(s) => Choice::write_field(handle, &s, config),
                     None => break,
                 };
 
@@ -100,12 +131,141 @@ impl Choice {
         }
     }
 
-    fn write_bytes<WriterType: Write>(handle: &mut BufWriter<WriterType>, b: &[u8]) {
-        match handle.write(b) {
+    /// Print a range with a non-negative start and a negative end, like `1:-3`, while streaming:
+    /// a field is printed once enough fields have followed it to know it comes before the end
+    fn print_until_from_end<'a, WriterType: Write>(
+        &self,
+        line_iter: impl Iterator<Item = Cow<'a, str>>,
+        config: &Config,
+        handle: &mut WriterType,
+    ) {
+        let start: usize = self.start.try_into().unwrap();
+        let behind: usize = (self.end.unsigned_abs()) - 1;
+        let mut delayed = VecDeque::new();
+
+        for (i, field) in line_iter.enumerate() {
+            delayed.push_back(field);
+            if delayed.len() <= behind {
+                continue;
+            }
+
+            let field = delayed.pop_front().unwrap();
+            if i - behind >= start {
+                Choice::write_field(handle, &field, config);
+            }
+        }
+    }
+
+    /// Print a range with a negative start, keeping only the fields it could reach in a bounded
+    /// window instead of every field of the line
+    fn print_from_end<'a, WriterType: Write>(
+        &self,
+        line_iter: impl Iterator<Item = Cow<'a, str>>,
+        config: &Config,
+        handle: &mut WriterType,
+    ) {
+        let (keep_last, keep_from) = if self.negative_end {
+            // only the fields this far from the end can be reached by either bound
+            let furthest = std::cmp::max(self.start.unsigned_abs(), self.end.unsigned_abs());
+            (furthest, usize::MAX)
+        } else {
+            // a positive end can make the range run backwards from the start down to the end,
+            // which needs every field from the end on
+            (
+                self.start.unsigned_abs(),
+                std::cmp::max(self.end, 0) as usize,
+            )
+        };
+
+        let mut window = VecDeque::new();
+        let mut first = 0;
+        for field in line_iter {
+            window.push_back(field);
+            while window.len() > keep_last && first < keep_from {
+                window.pop_front();
+                first += 1;
+            }
+        }
+
+        let len = first + window.len();
+        for i in self.indices(len) {
+            Choice::write_field(handle, &window[i - first], config);
+        }
+    }
+
+    /// Print this choice from a line that has already been split into `fields`, so that several
+    /// choices can share one pass of the field separator over the line. Each field is followed
+    /// by the output separator of `config`.
+    pub(crate) fn print_fields<WriterType: Write>(
+        &self,
+        line: &str,
+        fields: &Fields,
+        config: &Config,
+        handle: &mut WriterType,
+    ) {
+        for i in self.indices(fields.len()) {
+            Choice::write_field(handle, fields.get(line, i), config);
+        }
+    }
+
+    /// The indices of the fields this choice selects from a line of `len` fields, in printing
+    /// order.
+    ///
+    /// Negative indices count back from the end of the line. Bounds beyond either end of the line
+    /// are clamped to it like in Python slices, so `-10:-1` prints a whole line of four fields,
+    /// while a range lying entirely outside the line, like `-10:-8` or `5:7` on that line, prints
+    /// nothing.
+    pub fn indices(&self, len: usize) -> Indices {
+        let len = len as isize;
+        let resolve = |i: isize, negative: bool| if negative { len + i } else { i };
+        let start = resolve(self.start, self.negative_start);
+        let end = resolve(self.end, self.negative_end);
+
+        // with a negative index, a range runs forwards when its end isn't before its start, and
+        // otherwise backwards only from a negative start; -x moving a positive end below zero
+        // leaves nothing to print
+        let reversed = if !self.has_negative_index() {
+            self.is_reverse_range()
+        } else if end >= start {
+            false
+        } else if self.negative_start && (self.end >= 0 || self.negative_end) {
+            true
+        } else {
+            // empty, since the end is before the start
+            return Indices::Forward(start..=end);
+        };
+
+        if reversed {
+            let (from, to) = (std::cmp::min(start, len - 1), std::cmp::max(end, 0));
+            Indices::Reversed((to..=from).rev())
+        } else {
+            let (from, to) = (std::cmp::max(start, 0), std::cmp::min(end, len - 1));
+            Indices::Forward(from..=to)
+        }
+    }
+
+    /// How many leading fields of a line this choice can print, or `None` if it may need all of
+    /// them
+    pub(crate) fn field_limit(&self) -> Option<usize> {
+        if self.has_negative_index() {
+            return None;
+        }
+
+        let last: usize = std::cmp::max(self.start, self.end).try_into().unwrap();
+        last.checked_add(1)
+    }
+
+    /// Write `field`, quoted when asked to with --quote-output, followed by the output separator
+    fn write_field<WriterType: Write>(handle: &mut WriterType, field: &str, config: &Config) {
+        let field = match config.opt.quote_output {
+            Some(QuoteStyle::Shell) => shell::quote(field),
+            None => Cow::Borrowed(field),
+        };
+        match handle.write_all(field.as_bytes()) {
             Ok(_) => (),
             Err(e) => eprintln!("Failed to write to output: {}", e),
         }
-        match handle.write(b" ") {
+        match handle.write_all(config.output_separator.as_bytes()) {
             Ok(_) => (),
             Err(e) => eprintln!("Failed to write to output: {}", e),
         }
@@ -115,8 +275,181 @@ impl Choice {
         self.reversed
     }
 
+    /// Whether the range prints its fields from the last to the first. Unlike
+    /// [`Choice::is_reverse_range`], a range from a positive start to a negative end, which never
+    /// runs backwards, is not.
+    pub(crate) fn runs_backwards(&self) -> bool {
+        self.reversed && (self.negative_start || !self.negative_end)
+    }
+
     pub fn has_negative_index(&self) -> bool {
-        self.negative_index
+        self.negative_start || self.negative_end
+    }
+}
+
+/// Describes the fields a choice selects, as shown by --debug
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.end < 0 && !self.negative_end {
+            // only -x moves a positive end below zero
+            write!(f, "nothing")
+        } else if self.start == self.end {
+            write!(f, "field {}", self.start)
+        } else if self.end == isize::max_value() {
+            write!(f, "fields {} to the end", self.start)
+        } else if self.runs_backwards() {
+            write!(f, "fields {} down to {}", self.start, self.end)
+        } else {
+            write!(f, "fields {} to {}", self.start, self.end)
+        }
+    }
+}
+
+impl FromStr for Choice {
+    type Err = ParseIntError;
+
+    /// Parse a choice written as `x`, `x:`, `:y` or `x:y`, where `x` and `y` are integers and an
+    /// empty side of the colon continues to the beginning or end of the line
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let cap = match PARSE_CHOICE_RE.captures_iter(src).next() {
+            Some(v) => v,
+            None => return src.parse().map(|x| Choice::new(x, x)),
+        };
+
+        let start = if cap[1].is_empty() {
+            0
+        } else {
+            cap[1].parse()?
+        };
+
+        let end = if cap[2].is_empty() {
+            isize::max_value()
+        } else {
+            cap[2].parse()?
+        };
+
+        Ok(Choice::new(start, end))
+    }
+}
+
+/// Explain why `src` failed to parse as a choice with `error`, pointing at the first character
+/// at fault and suggesting a fix for common mistakes like `1-3`, `1..3` or `$1`
+pub fn diagnose(src: &str, error: &ParseIntError) -> Diagnostic {
+    let column = |byte: usize| src[..byte].chars().count();
+
+    if src.is_empty() {
+        return Diagnostic::new(src, 0, "empty choice")
+            .help("give an index like 0 or a range like 1:3");
+    }
+    if let Some(cap) = DASH_RANGE_RE.captures(src) {
+        let dash = cap.get(1).unwrap().end();
+        return Diagnostic::new(src, column(dash), "unexpected `-`").help(format!(
+            "ranges are written with a colon: {}:{}",
+            &cap[1], &cap[2]
+        ));
+    }
+    if let Some(cap) = DOT_RANGE_RE.captures(src) {
+        let dots = cap.get(1).unwrap().end();
+        let suggestion = match (&cap[2], cap[3].parse::<isize>()) {
+            ("=", _) | (_, Err(_)) => format!("{}:{}", &cap[1], &cap[3]),
+            (_, Ok(end)) if end > 0 => format!(
+                "{}:{}, or -x {}:{} to leave out the end",
+                &cap[1],
+                end - 1,
+                &cap[1],
+                end
+            ),
+            (_, Ok(end)) => format!("-x {}:{} to leave out the end", &cap[1], end),
+        };
+        return Diagnostic::new(src, column(dots), "unexpected `..`").help(format!(
+            "ranges are written with a colon and include both ends: {}",
+            suggestion
+        ));
+    }
+    if let Some(cap) = AWK_FIELD_RE.captures(src) {
+        let help = match cap[1].parse::<usize>() {
+            Ok(0) => String::from("fields need no `$`, and the whole line is :"),
+            Ok(n) => format!(
+                "fields need no `$` and are counted from 0, so awk's ${} is {}",
+                n,
+                n - 1
+            ),
+            Err(_) => String::from("fields need no `$`"),
+        };
+        return Diagnostic::new(src, 0, "unexpected `$`").help(help);
+    }
+    if src.contains(',') && src.split(',').all(|c| c.parse::<Choice>().is_ok()) {
+        let comma = src.find(',').unwrap();
+        return Diagnostic::new(src, column(comma), "unexpected `,`").help(format!(
+            "give each choice as its own argument: {}",
+            src.replace(',', " ")
+        ));
+    }
+    if NAME_RE.is_match(src) {
+        let presets: Vec<&str> = PRESETS
+            .iter()
+            .filter(|p| p.column(src).is_some())
+            .map(|p| p.name)
+            .collect();
+        let diagnostic = Diagnostic::new(src, 0, "not a number");
+        return match presets.as_slice() {
+            [] => diagnostic.help("column names need a --preset"),
+            presets => diagnostic.help(format!(
+                "column names need a --preset; {} is a column of {}",
+                src,
+                presets.join(", ")
+            )),
+        };
+    }
+
+    let mut colons = 0;
+    let mut bound_start = 0;
+    for (i, c) in src.char_indices() {
+        match c {
+            '0'..='9' => (),
+            '-' if i == bound_start => (),
+            ':' if colons == 0 => {
+                if i > bound_start && &src[bound_start..i] == "-" {
+                    return Diagnostic::new(src, column(bound_start), "`-` without a number");
+                }
+                colons += 1;
+                bound_start = i + 1;
+            }
+            ':' => {
+                return Diagnostic::new(src, column(i), "a second `:`")
+                    .help("a range has a single colon between its start and end");
+            }
+            c => return Diagnostic::new(src, column(i), format!("unexpected `{}`", c)),
+        }
+    }
+    if &src[bound_start..] == "-" {
+        return Diagnostic::new(src, column(bound_start), "`-` without a number");
+    }
+
+    match error.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+            Diagnostic::new(src, 0, "index out of range")
+        }
+        _ => Diagnostic::new(src, 0, error.to_string()),
+    }
+}
+
+/// Iterator over the indices of the fields a [`Choice`] selects from a line
+#[derive(Debug, Clone)]
+pub enum Indices {
+    Forward(RangeInclusive<isize>),
+    Reversed(Rev<RangeInclusive<isize>>),
+}
+
+impl Iterator for Indices {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self {
+            Indices::Forward(r) => r.next(),
+            Indices::Reversed(r) => r.next(),
+        }
+        .map(|i| i as usize)
     }
 }
 
@@ -135,7 +468,7 @@ mod tests {
             I: IntoIterator,
             I::Item: Into<OsString> + Clone,
         {
-            return Config::new(Opt::from_iter(iter));
+            Config::new(Opt::from_iter(iter)).unwrap()
         }
     }
 
@@ -183,7 +516,7 @@ mod tests {
             let config = Config::from_iter(vec!["choose", "0"]);
             let mut handle = BufWriter::new(MockStdout::new());
 
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust is pretty cool"),
                 &config,
                 &mut handle,
@@ -200,7 +533,7 @@ mod tests {
             let config = Config::from_iter(vec!["choose", "10"]);
             let mut handle = BufWriter::new(MockStdout::new());
 
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust is pretty cool"),
                 &config,
                 &mut handle,
@@ -215,7 +548,7 @@ mod tests {
             let mut handle = BufWriter::new(MockStdout::new());
             let mut handle1 = BufWriter::new(MockStdout::new());
 
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust is pretty cool"),
                 &config,
                 &mut handle,
@@ -226,7 +559,7 @@ mod tests {
                 MockStdout::str_from_buf_writer(handle)
             );
 
-            config.opt.choice[1].print_choice(
+            config.choices[1].print_choice(
                 &String::from("rust is pretty cool"),
                 &config,
                 &mut handle1,
@@ -239,7 +572,7 @@ mod tests {
         fn print_1_to_3_exclusive() {
             let config = Config::from_iter(vec!["choose", "1:3", "-x"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust is pretty cool"),
                 &config,
                 &mut handle,
@@ -254,7 +587,7 @@ mod tests {
         fn print_1_to_3() {
             let config = Config::from_iter(vec!["choose", "1:3"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust is pretty cool"),
                 &config,
                 &mut handle,
@@ -269,7 +602,7 @@ mod tests {
         fn print_1_to_3_separated_by_hashtag() {
             let config = Config::from_iter(vec!["choose", "1:3", "-f", "#"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust#is#pretty#cool"),
                 &config,
                 &mut handle,
@@ -284,7 +617,7 @@ mod tests {
         fn print_1_to_3_separated_by_varying_multiple_hashtag_exclusive() {
             let config = Config::from_iter(vec!["choose", "1:3", "-f", "#", "-x"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust##is###pretty####cool"),
                 &config,
                 &mut handle,
@@ -299,7 +632,7 @@ mod tests {
         fn print_1_to_3_separated_by_varying_multiple_hashtag() {
             let config = Config::from_iter(vec!["choose", "1:3", "-f", "#"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust##is###pretty####cool"),
                 &config,
                 &mut handle,
@@ -314,7 +647,7 @@ mod tests {
         fn print_1_to_3_separated_by_regex_group_vowels_exclusive() {
             let config = Config::from_iter(vec!["choose", "1:3", "-f", "[aeiou]", "-x"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("the quick brown fox jumped over the lazy dog"),
                 &config,
                 &mut handle,
@@ -329,7 +662,7 @@ mod tests {
         fn print_1_to_3_separated_by_regex_group_vowels() {
             let config = Config::from_iter(vec!["choose", "1:3", "-f", "[aeiou]"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("the quick brown fox jumped over the lazy dog"),
                 &config,
                 &mut handle,
@@ -344,7 +677,7 @@ mod tests {
         fn print_3_to_1() {
             let config = Config::from_iter(vec!["choose", "3:1"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -359,7 +692,7 @@ mod tests {
         fn print_3_to_1_exclusive() {
             let config = Config::from_iter(vec!["choose", "3:1", "-x"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -374,7 +707,7 @@ mod tests {
         fn print_1_to_3_nonexistant_field_separator() {
             let config = Config::from_iter(vec!["choose", "1:3", "-f", "#"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -386,7 +719,7 @@ mod tests {
         fn print_0_nonexistant_field_separator() {
             let config = Config::from_iter(vec!["choose", "0", "-f", "#"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -401,7 +734,7 @@ mod tests {
         fn print_0_to_3_nonexistant_field_separator() {
             let config = Config::from_iter(vec!["choose", "0:3", "-f", "#"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -416,7 +749,7 @@ mod tests {
         fn print_0_with_preceding_separator() {
             let config = Config::from_iter(vec!["choose", "0"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("   rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -431,7 +764,7 @@ mod tests {
         fn print_neg3_to_neg1() {
             let config = Config::from_iter(vec!["choose", "-3:-1"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -446,7 +779,7 @@ mod tests {
         fn print_neg1_to_neg3() {
             let config = Config::from_iter(vec!["choose", "-1:-3"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -461,7 +794,7 @@ mod tests {
         fn print_neg2_to_end() {
             let config = Config::from_iter(vec!["choose", "-2:"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -476,7 +809,7 @@ mod tests {
         fn print_start_to_neg3() {
             let config = Config::from_iter(vec!["choose", ":-3"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -491,7 +824,7 @@ mod tests {
         fn print_1_to_neg3() {
             let config = Config::from_iter(vec!["choose", "1:-3"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -506,7 +839,7 @@ mod tests {
         fn print_5_to_neg3_empty() {
             let config = Config::from_iter(vec!["choose", "5:-3"]);
             let mut handle = BufWriter::new(MockStdout::new());
-            config.opt.choice[0].print_choice(
+            config.choices[0].print_choice(
                 &String::from("rust lang is pretty darn cool"),
                 &config,
                 &mut handle,
@@ -515,37 +848,480 @@ mod tests {
         }
     }
 
+    mod long_line_tests {
+        use super::*;
+
+        fn long_line() -> String {
+            (0..50_000)
+                .map(|i| format!("f{}", i))
+                .collect::<Vec<String>>()
+                .join(" ")
+        }
+
+        fn print(args: &[&str], line: &str) -> String {
+            let mut argv = vec!["choose"];
+            argv.extend(args);
+            let config = Config::from_iter(argv);
+            let mut handle = BufWriter::new(MockStdout::new());
+            config.choices[0].print_choice(line, &config, &mut handle);
+            MockStdout::str_from_buf_writer(handle)
+        }
+
+        #[test]
+        fn print_neg1_long_line() {
+            assert_eq!("f49999", print(&["-1"], &long_line()));
+        }
+
+        #[test]
+        fn print_neg3_to_neg1_long_line() {
+            assert_eq!("f49997 f49998 f49999", print(&["-3:-1"], &long_line()));
+        }
+
+        #[test]
+        fn print_neg1_to_neg3_long_line() {
+            assert_eq!("f49999 f49998 f49997", print(&["-1:-3"], &long_line()));
+        }
+
+        #[test]
+        fn print_neg50000_long_line() {
+            assert_eq!("f0", print(&["-50000"], &long_line()));
+        }
+
+        #[test]
+        fn print_neg2_to_end_long_line() {
+            assert_eq!("f49998 f49999", print(&["-2:"], &long_line()));
+        }
+
+        #[test]
+        fn print_49990_to_neg8_long_line() {
+            assert_eq!("f49990 f49991 f49992", print(&["49990:-8"], &long_line()));
+        }
+
+        #[test]
+        fn print_49996_to_neg3_long_line() {
+            assert_eq!("f49996 f49997", print(&["49996:-3"], &long_line()));
+        }
+
+        #[test]
+        fn print_neg3_to_49998_long_line() {
+            assert_eq!("f49997 f49998", print(&["-3:49998"], &long_line()));
+        }
+
+        #[test]
+        fn print_neg1_to_49997_long_line() {
+            assert_eq!("f49999 f49998 f49997", print(&["-1:49997"], &long_line()));
+        }
+
+        #[test]
+        fn print_5_to_neg49990_long_line() {
+            let expected: Vec<String> = (5..=10).map(|i| format!("f{}", i)).collect();
+            assert_eq!(expected.join(" "), print(&["5:-49990"], &long_line()));
+        }
+
+        #[test]
+        fn print_matches_fields_long_line() {
+            let line = long_line();
+            for choice in ["-1", "-49999:-49990", "-5:-10", "3:-49990", "-4:49997"].iter() {
+                let config = Config::from_iter(vec!["choose", choice]);
+                let mut fields = crate::fields::Fields::new();
+                fields.split(&line, &config.tokenizer, None);
+                let mut handle = BufWriter::new(MockStdout::new());
+                config.choices[0].print_fields(&line, &fields, &config, &mut handle);
+
+                assert_eq!(
+                    MockStdout::str_from_buf_writer(handle),
+                    print(&[choice], &line),
+                    "choice {}",
+                    choice
+                );
+            }
+        }
+    }
+
+    mod out_of_range_tests {
+        use super::*;
+
+        fn print(args: &[&str], line: &str) -> String {
+            let mut argv = vec!["choose"];
+            argv.extend(args);
+            let config = Config::from_iter(argv);
+            let mut handle = BufWriter::new(MockStdout::new());
+            config.choices[0].print_choice(line, &config, &mut handle);
+            MockStdout::str_from_buf_writer(handle)
+        }
+
+        #[test]
+        fn print_neg10_short_line() {
+            assert_eq!("", print(&["-10"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg10_to_neg1_short_line() {
+            assert_eq!("rust is", print(&["-10:-1"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg10_to_neg8_short_line() {
+            assert_eq!("", print(&["-10:-8"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg1_to_neg10_short_line() {
+            assert_eq!("is rust", print(&["-1:-10"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg8_to_neg10_short_line() {
+            assert_eq!("", print(&["-8:-10"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg10_to_end_short_line() {
+            assert_eq!("rust is", print(&["-10:"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg10_to_1_short_line() {
+            assert_eq!("rust is", print(&["-10:1"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg1_to_0_short_line() {
+            assert_eq!("is rust", print(&["-1:0"], "rust is"));
+        }
+
+        #[test]
+        fn print_0_to_neg10_short_line() {
+            assert_eq!("", print(&["0:-10"], "rust is"));
+        }
+
+        #[test]
+        fn print_1_to_neg5_single_field() {
+            assert_eq!("lang", print(&["1:-5"], "rust lang is pretty darn cool"));
+        }
+
+        #[test]
+        fn print_smallest_bounds_exclusive() {
+            let line = "rust lang is pretty darn cool";
+            assert_eq!("", print(&["0:-9223372036854775808", "-x"], line));
+            assert_eq!("", print(&["-9223372036854775808", "-x"], line));
+        }
+
+        #[test]
+        fn print_5_to_neg3_exclusive_empty() {
+            assert_eq!("", print(&["5:-3", "-x"], "rust lang is pretty darn cool"));
+        }
+
+        #[test]
+        fn print_1_to_neg1_exclusive() {
+            assert_eq!(
+                "lang is pretty darn",
+                print(&["1:-1", "-x"], "rust lang is pretty darn cool")
+            );
+        }
+
+        #[test]
+        fn print_0_exclusive_empty() {
+            assert_eq!("", print(&["0", "-x"], "rust is"));
+        }
+
+        #[test]
+        fn print_neg3_to_0_exclusive_empty() {
+            assert_eq!("", print(&["-3:0", "-x"], "rust lang is"));
+        }
+
+        #[test]
+        fn print_5_to_7_short_line() {
+            assert_eq!("", print(&["5:7"], "rust is"));
+        }
+
+        #[test]
+        fn print_7_to_5_short_line() {
+            assert_eq!("", print(&["7:5"], "rust is"));
+        }
+
+        #[test]
+        fn print_7_to_0_short_line() {
+            assert_eq!("is rust", print(&["7:0"], "rust is"));
+        }
+
+        #[test]
+        fn print_every_branch_empty_line() {
+            for choice in [
+                "0", "3:1", "1:3", "-1", "-1:-3", "-3:-1", "-2:", "1:-1", "-2:3",
+            ]
+            .iter()
+            {
+                assert_eq!("", print(&[choice], ""), "choice {}", choice);
+                assert_eq!("", print(&[choice], "   "), "choice {}", choice);
+            }
+        }
+    }
+
+    mod print_fields_tests {
+        use super::*;
+        use crate::fields::Fields;
+
+        fn print_both(args: &[&str], line: &str) -> (String, String) {
+            let mut argv = vec!["choose"];
+            argv.extend(args);
+            let config = Config::from_iter(argv);
+            let choice = &config.choices[0];
+
+            let mut streamed = BufWriter::new(MockStdout::new());
+            choice.print_choice(line, &config, &mut streamed);
+
+            let mut fields = Fields::new();
+            fields.split(line, &config.tokenizer, choice.field_limit());
+            let mut indexed = BufWriter::new(MockStdout::new());
+            choice.print_fields(line, &fields, &config, &mut indexed);
+
+            (
+                MockStdout::str_from_buf_writer(streamed),
+                MockStdout::str_from_buf_writer(indexed),
+            )
+        }
+
+        #[test]
+        fn print_fields_matches_print_choice() {
+            let choices = [
+                "0", "3", "9", ":", "1:3", "3:1", "0:0", "2:", ":2", "8:2", "-1", "-5", "-3:-1",
+                "-1:-3", "-2:", ":-3", "1:-3", "5:-3", "-3:5", "-1:2", "-5:-5",
+            ];
+            let lines = [
+                "rust lang is pretty darn cool",
+                "  rust lang   is pretty darn cool and fast  ",
+            ];
+
+            for choice in choices.iter() {
+                for line in lines.iter() {
+                    let (streamed, indexed) = print_both(&[choice], line);
+                    assert_eq!(streamed, indexed, "choice {} on {:?}", choice, line);
+
+                    let (streamed, indexed) = print_both(&[choice, "-x"], line);
+                    assert_eq!(streamed, indexed, "choice {} -x on {:?}", choice, line);
+                }
+            }
+        }
+
+        #[test]
+        fn print_fields_short_line() {
+            let choices = [
+                "0", "3", "3:1", "1:", "-10", "-1:-3", "-10:-1", "1:-1", "-2:3", "-1:0",
+            ];
+            for choice in choices.iter() {
+                for line in ["rust", "rust is", ""].iter() {
+                    let (streamed, indexed) = print_both(&[choice], line);
+                    assert_eq!(streamed, indexed, "choice {} on {:?}", choice, line);
+                }
+            }
+        }
+
+        #[test]
+        fn field_limit_positive() {
+            let config = Config::from_iter(vec!["choose", "4:2"]);
+            assert_eq!(Some(5), config.choices[0].field_limit());
+        }
+
+        #[test]
+        fn field_limit_negative() {
+            let config = Config::from_iter(vec!["choose", "2:-1"]);
+            assert_eq!(None, config.choices[0].field_limit());
+        }
+    }
+
+    #[allow(clippy::bool_assert_comparison)]
     mod is_reverse_range_tests {
         use super::*;
 
         #[test]
         fn is_field_reversed() {
             let config = Config::from_iter(vec!["choose", "0"]);
-            assert_eq!(false, config.opt.choice[0].is_reverse_range());
+            assert_eq!(false, config.choices[0].is_reverse_range());
         }
 
         #[test]
         fn is_field_range_no_start_reversed() {
             let config = Config::from_iter(vec!["choose", ":2"]);
-            assert_eq!(false, config.opt.choice[0].is_reverse_range());
+            assert_eq!(false, config.choices[0].is_reverse_range());
         }
 
         #[test]
         fn is_field_range_no_end_reversed() {
             let config = Config::from_iter(vec!["choose", "2:"]);
-            assert_eq!(false, config.opt.choice[0].is_reverse_range());
+            assert_eq!(false, config.choices[0].is_reverse_range());
         }
 
         #[test]
         fn is_field_range_no_start_or_end_reversed() {
             let config = Config::from_iter(vec!["choose", ":"]);
-            assert_eq!(false, config.opt.choice[0].is_reverse_range());
+            assert_eq!(false, config.choices[0].is_reverse_range());
         }
 
         #[test]
         fn is_reversed_field_range_reversed() {
             let config = Config::from_iter(vec!["choose", "4:2"]);
-            assert_eq!(true, config.opt.choice[0].is_reverse_range());
+            assert_eq!(true, config.choices[0].is_reverse_range());
+        }
+    }
+
+    mod display_tests {
+        use super::*;
+
+        fn describe(args: Vec<&str>) -> String {
+            let mut argv = vec!["choose"];
+            argv.extend(args);
+            Config::from_iter(argv).choices[0].to_string()
+        }
+
+        #[test]
+        fn display_field() {
+            assert_eq!("field -1", describe(vec!["-1"]));
+        }
+
+        #[test]
+        fn display_range_to_end() {
+            assert_eq!("fields 2 to the end", describe(vec!["2:"]));
+        }
+
+        #[test]
+        fn display_reversed_range() {
+            assert_eq!("fields 4 down to 2", describe(vec!["4:2"]));
+        }
+
+        #[test]
+        fn display_exclusive_range() {
+            assert_eq!("fields 1 to 2", describe(vec!["-x", "1:3"]));
+        }
+
+        #[test]
+        fn display_exclusive_range_to_end() {
+            assert_eq!("fields 2 to the end", describe(vec!["-x", "2:"]));
+        }
+
+        #[test]
+        fn display_empty_exclusive_range() {
+            assert_eq!("nothing", describe(vec!["-x", ":0"]));
+        }
+    }
+
+    mod diagnose_tests {
+        use crate::choice::Choice;
+        use crate::diagnostic::Diagnostic;
+
+        fn diagnose(src: &str) -> Diagnostic {
+            let error = src.parse::<Choice>().unwrap_err();
+            crate::choice::diagnose(src, &error)
+        }
+
+        #[test]
+        fn diagnose_dash_range() {
+            let d = diagnose("1-3");
+            assert_eq!((1, "unexpected `-`"), (d.column, d.message.as_str()));
+            assert_eq!(
+                Some("ranges are written with a colon: 1:3"),
+                d.help.as_deref()
+            );
+        }
+
+        #[test]
+        fn diagnose_dash_range_negative_end() {
+            let d = diagnose("1--3");
+            assert_eq!(1, d.column);
+            assert!(d.help.unwrap().ends_with("1:-3"));
+        }
+
+        #[test]
+        fn diagnose_exclusive_dot_range() {
+            let d = diagnose("1..3");
+            assert_eq!((1, "unexpected `..`"), (d.column, d.message.as_str()));
+            assert!(d
+                .help
+                .unwrap()
+                .ends_with("1:2, or -x 1:3 to leave out the end"));
+        }
+
+        #[test]
+        fn diagnose_inclusive_dot_range() {
+            assert!(diagnose("2..=4").help.unwrap().ends_with(": 2:4"));
+        }
+
+        #[test]
+        fn diagnose_open_dot_range() {
+            assert!(diagnose("2..").help.unwrap().ends_with(": 2:"));
+        }
+
+        #[test]
+        fn diagnose_awk_field() {
+            let d = diagnose("$3");
+            assert_eq!((0, "unexpected `$`"), (d.column, d.message.as_str()));
+            assert!(d.help.unwrap().ends_with("so awk's $3 is 2"));
+        }
+
+        #[test]
+        fn diagnose_awk_whole_line() {
+            assert!(diagnose("$0")
+                .help
+                .unwrap()
+                .ends_with("the whole line is :"));
+        }
+
+        #[test]
+        fn diagnose_comma_list() {
+            let d = diagnose("0,2,-1");
+            assert_eq!((1, "unexpected `,`"), (d.column, d.message.as_str()));
+            assert!(d.help.unwrap().ends_with(": 0 2 -1"));
+        }
+
+        #[test]
+        fn diagnose_column_name() {
+            let d = diagnose("shell");
+            assert_eq!((0, "not a number"), (d.column, d.message.as_str()));
+            assert_eq!(
+                Some("column names need a --preset; shell is a column of passwd"),
+                d.help.as_deref()
+            );
+        }
+
+        #[test]
+        fn diagnose_unknown_name() {
+            assert_eq!(
+                Some("column names need a --preset"),
+                diagnose("nope").help.as_deref()
+            );
+        }
+
+        #[test]
+        fn diagnose_second_colon() {
+            let d = diagnose("1:2:3");
+            assert_eq!((3, "a second `:`"), (d.column, d.message.as_str()));
+        }
+
+        #[test]
+        fn diagnose_lone_dash() {
+            assert_eq!(0, diagnose("-:3").column);
+            assert_eq!(2, diagnose("3:-").column);
+            assert_eq!("`-` without a number", diagnose("-").message);
+        }
+
+        #[test]
+        fn diagnose_unexpected_character() {
+            let d = diagnose("4:1x");
+            assert_eq!((3, "unexpected `x`"), (d.column, d.message.as_str()));
+            assert_eq!(None, d.help);
+        }
+
+        #[test]
+        fn diagnose_overflow() {
+            assert_eq!(
+                "index out of range",
+                diagnose("99999999999999999999").message
+            );
+        }
+
+        #[test]
+        fn diagnose_empty() {
+            assert_eq!("empty choice", diagnose("").message);
         }
     }
 }
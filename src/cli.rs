@@ -0,0 +1,210 @@
+use memmap2::Mmap;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::process;
+use structopt::StructOpt;
+
+use crate::config::Config;
+use crate::error::ChooseError;
+#[cfg(unix)]
+use crate::interactive;
+use crate::opt::{Command, Opt};
+use crate::settings::Settings;
+use crate::tokenizer::{Tokenizer, Widths};
+use crate::writer::{LineWriter, SplitWriter};
+use crate::{debug, man, preview, reader};
+
+/// Run the `choose` command line tool, exiting with a code for the kind of failure if it fails
+pub fn main() {
+    if let Err(e) = parse_args().and_then(|opt| opt.map_or(Ok(()), run)) {
+        eprintln!("{}", e);
+        process::exit(exit_code(&e));
+    }
+}
+
+/// The exit code reported for each kind of failure
+fn exit_code(e: &ChooseError) -> i32 {
+    match e {
+        ChooseError::BadChoice(..) | ChooseError::Settings(..) | ChooseError::UnknownColumn(..) => {
+            1
+        }
+        ChooseError::BadRegex(_) | ChooseError::BadSeparator(..) => 2,
+        ChooseError::Open(_) | ChooseError::Read(_) | ChooseError::Terminal(_) => 3,
+        ChooseError::Write(_) => 4,
+    }
+}
+
+/// Parse the command line, after expanding aliases and adding defaults from the environment and
+/// the config file. Returns `None` once --print-config has printed the result instead.
+fn parse_args() -> Result<Option<Opt>, ChooseError> {
+    let mut settings = Settings::load()?;
+    settings.read_env(env::vars_os());
+    let given = settings.expand_aliases(env::args_os().collect());
+    let args = settings.apply_defaults(given.clone());
+    let opt = Opt::from_iter(&args);
+
+    if opt.print_config {
+        let mut handle = io::stdout().lock();
+        settings
+            .print_config(&given, &args, &mut handle)
+            .map_err(ChooseError::Write)?;
+        return Ok(None);
+    }
+    Ok(Some(opt))
+}
+
+fn run(opt: Opt) -> Result<(), ChooseError> {
+    if let Some(Command::Completions { shell }) = opt.command {
+        Opt::clap().gen_completions_to("choose", shell, &mut io::stdout());
+        return Ok(());
+    }
+    if opt.man {
+        return man::write_man(Opt::clap(), &mut io::stdout().lock()).map_err(ChooseError::Write);
+    }
+
+    let mut config = Config::new(opt)?;
+
+    let mut input = match &config.opt.input {
+        Some(f) => Some(File::open(f).map_err(ChooseError::Open)?),
+        None => None,
+    };
+    // pipes and other special files can be neither mapped nor split into chunks
+    let regular_file = input
+        .as_ref()
+        .is_some_and(|f| f.metadata().is_ok_and(|m| m.is_file()));
+
+    // the lines the columns are found from are read ahead, and printed before the rest
+    let mut sample = Vec::new();
+    let mut sampled_input = None;
+    if let Some(n) = config.opt.auto_columns {
+        let mut reader = stream_input(&config, input.take());
+        sample = read_sample(&mut reader, n);
+        config.tokenizer = Tokenizer::Widths(Widths::infer(&sample));
+        sampled_input = Some(reader);
+    }
+
+    if config.opt.debug {
+        debug::print_config(&config, &mut io::stderr().lock()).map_err(ChooseError::Write)?;
+    }
+    if let Some(n) = config.opt.preview {
+        let mut reader = sampled_input.unwrap_or_else(|| stream_input(&config, input));
+        sample.truncate(n);
+        sample.extend(read_sample(&mut reader, n - sample.len()));
+        return preview(&config, &sample);
+    }
+    if config.opt.interactive {
+        return interactive(&config, stream_input(&config, input));
+    }
+
+    let stdout = io::stdout();
+    // flush every line when writing to a terminal so interactive pipelines stay live; lines split
+    // into files don't go to the terminal, so those are only flushed when asked to
+    let line_buffered =
+        config.opt.line_buffered || (config.split_by.is_none() && stdout.is_terminal());
+    let lock = stdout.lock();
+
+    let split_writer = match &config.opt.out_dir {
+        Some(dir) => Some(
+            SplitWriter::new(dir.clone(), config.opt.max_open_files).map_err(ChooseError::Write)?,
+        ),
+        None => None,
+    };
+    let mut output = LineWriter::new(&config, lock, split_writer, line_buffered);
+
+    if let Some(map) = input.as_ref().filter(|_| regular_file).and_then(map_file) {
+        for record in reader::Records::new(&map, &config.input_terminator) {
+            match std::str::from_utf8(record) {
+                Ok(l) => output.write_line(l).map_err(ChooseError::Write)?,
+                Err(e) => println!("Failed to read line: {}", e),
+            }
+        }
+    } else {
+        for line in &sample {
+            output.write_line(line).map_err(ChooseError::Write)?;
+        }
+        let mut reader = sampled_input.unwrap_or_else(|| stream_input(&config, input));
+        let mut buffer = String::new();
+
+        while let Some(line) = reader.read_line(&mut buffer) {
+            match line {
+                Ok(l) => output.write_line(l).map_err(ChooseError::Write)?,
+                Err(e) => println!("Failed to read line: {}", e),
+            }
+        }
+    }
+
+    output.flush().map_err(ChooseError::Write)
+}
+
+/// Print `lines` with their fields labelled by index
+fn preview(config: &Config, lines: &[String]) -> Result<(), ChooseError> {
+    let mut handle = io::BufWriter::new(io::stdout().lock());
+    if config.opt.table {
+        preview::print_table(config, lines, &mut handle)
+    } else {
+        preview::print_inline(config, lines, &mut handle)
+    }
+    .and_then(|_| handle.flush())
+    .map_err(ChooseError::Write)
+}
+
+/// Let the user pick columns from a sample of the input, then print the matching command
+#[cfg(unix)]
+fn interactive<R: Read>(
+    config: &Config,
+    mut reader: reader::BufReader<R>,
+) -> Result<(), ChooseError> {
+    let lines = read_sample(&mut reader, interactive::SAMPLE_LINES);
+    let mut picker =
+        interactive::Picker::new(lines, config.opt.field_separator.clone(), &config.choices)?;
+
+    // keys come from the terminal, as stdin may well be the input
+    let tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(ChooseError::Terminal)?;
+    if let Some(command) = interactive::run(&tty, &mut picker).map_err(ChooseError::Terminal)? {
+        println!("{}", command);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn interactive<R: Read>(_: &Config, _: reader::BufReader<R>) -> Result<(), ChooseError> {
+    Err(ChooseError::Terminal(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--interactive needs a Unix terminal",
+    )))
+}
+
+/// The first `n` records of the input
+fn read_sample<R: Read>(reader: &mut reader::BufReader<R>, n: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut buffer = String::new();
+    while lines.len() < n {
+        match reader.read_line(&mut buffer) {
+            Some(Ok(l)) => lines.push(l.clone()),
+            Some(Err(e)) => println!("Failed to read line: {}", e),
+            None => break,
+        }
+    }
+    lines
+}
+
+/// Read records from the input file, or stdin when none was given, through a buffer
+fn stream_input(config: &Config, input: Option<File>) -> reader::BufReader<Box<dyn Read>> {
+    let read = match input {
+        Some(fh) => Box::new(fh) as Box<dyn Read>,
+        None => Box::new(io::stdin()) as Box<dyn Read>,
+    };
+    reader::BufReader::new(read, config.input_terminator.clone())
+}
+
+/// Map a regular file into memory, so its records can be read without copying them
+fn map_file(file: &File) -> Option<Mmap> {
+    // SAFETY: the mapping is only read, and like other tools reading files this way we accept
+    // that a file truncated or rewritten by another process while mapped may misbehave
+    unsafe { Mmap::map(file) }.ok()
+}
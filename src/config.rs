@@ -1,95 +1,152 @@
-use regex::Regex;
-use std::num::ParseIntError;
-use std::process;
+use regex::RegexBuilder;
 
 use crate::choice::Choice;
+use crate::diagnostic;
+use crate::error::ChooseError;
+use crate::fields::MaxSplit;
 use crate::opt::Opt;
-
-lazy_static! {
-    static ref PARSE_CHOICE_RE: Regex = Regex::new(r"^(-?\d*):(-?\d*)$").unwrap();
-}
+use crate::preset::Preset;
+use crate::reader::Terminator;
+use crate::tokenizer::Tokenizer;
 
 pub struct Config {
     pub opt: Opt,
-    pub separator: Regex,
+    pub choices: Vec<Choice>,
+    pub split_by: Option<Choice>,
+    pub tokenizer: Tokenizer,
+    pub max_split: Option<MaxSplit>,
+    pub output_separator: String,
+    pub input_terminator: Terminator,
+    pub output_terminator: u8,
 }
 
 impl Config {
-    pub fn new(mut opt: Opt) -> Self {
+    pub fn new(opt: Opt) -> Result<Self, ChooseError> {
+        let mut choices = opt
+            .choice
+            .iter()
+            .map(|c| Config::resolve_choice(c, opt.preset))
+            .collect::<Result<Vec<_>, _>>()?;
+        let split_by = match &opt.split_by {
+            Some(c) => Some(Config::resolve_choice(c, opt.preset)?),
+            None => None,
+        };
+
         if opt.exclusive {
-            for mut choice in &mut opt.choice {
-                if choice.is_reverse_range() {
-                    choice.start = choice.start - 1;
-                } else {
-                    choice.end = choice.end - 1;
-                }
+            for choice in &mut choices {
+                choice.exclude_end();
             }
         }
 
-        let separator = match Regex::new(match &opt.field_separator {
-            Some(s) => s,
-            None => "[[:space:]]",
-        }) {
-            Ok(r) => r,
-            Err(e) => {
-                // Exit code of 2 means failed to compile field_separator regex
-                match e {
-                    regex::Error::Syntax(e) => {
-                        eprintln!("Syntax error compiling regular expression: {}", e);
-                        process::exit(2);
-                    }
-                    regex::Error::CompiledTooBig(e) => {
-                        eprintln!("Compiled regular expression too big: compiled size cannot exceed {} bytes", e);
-                        process::exit(2);
-                    }
-                    _ => {
-                        eprintln!("Error compiling regular expression: {}", e);
-                        process::exit(2);
-                    }
-                }
+        let build = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(opt.ignore_case)
+                .build()
+        };
+        // records that span several lines always treat newlines as field separators
+        let multiline_records = opt.paragraph || opt.record_separator.is_some();
+        let tokenizer = match (&opt.widths, opt.preset, &opt.field_separator) {
+            (Some(widths), _, _) => Tokenizer::Widths(widths.clone()),
+            (None, None, None) if opt.shell_words => Tokenizer::ShellWords,
+            (None, Some(preset), _) if preset.pattern => Tokenizer::Pattern(build(preset.regex)?),
+            (None, Some(preset), _) if preset.keep_empty => {
+                Tokenizer::Delimiter(build(preset.regex)?)
+            }
+            (None, Some(preset), _) => Tokenizer::Separator(build(preset.regex)?),
+            (None, None, Some(s)) => {
+                let separator =
+                    build(s).map_err(|e| Config::regex_error("--field-separator", s, e))?;
+                Tokenizer::Separator(if multiline_records {
+                    build(&format!("(?:{})|\n", s))?
+                } else {
+                    separator
+                })
             }
+            (None, None, None) if opt.unicode_space => Tokenizer::Separator(build(r"\s")?),
+            (None, None, None) => Tokenizer::Separator(build("[[:space:]]")?),
         };
 
-        Config { opt, separator }
-    }
-
-    pub fn parse_choice(src: &str) -> Result<Choice, ParseIntError> {
-        let cap = match PARSE_CHOICE_RE.captures_iter(src).next() {
-            Some(v) => v,
-            None => match src.parse() {
-                Ok(x) => return Ok(Choice::new(x, x)),
-                Err(e) => {
-                    eprintln!("failed to parse choice argument: {}", src);
-                    return Err(e);
-                }
-            },
+        let max_split = match (opt.max_split, opt.rmax_split) {
+            (Some(n), _) => Some(MaxSplit::Left(n)),
+            (None, Some(n)) => Some(MaxSplit::Right(n)),
+            (None, None) => opt.preset.and_then(|p| p.max_split).map(MaxSplit::Left),
         };
 
-        let start = if cap[1].is_empty() {
-            0
+        let input_terminator = if opt.paragraph {
+            Terminator::Paragraph
+        } else if let Some(s) = &opt.record_separator {
+            Terminator::Regex(
+                regex::bytes::RegexBuilder::new(s)
+                    .case_insensitive(opt.ignore_case)
+                    .build()
+                    .map_err(|e| Config::regex_error("--record-separator", s, e))?,
+            )
+        } else if opt.zero_terminated || opt.null_input {
+            Terminator::Byte(b'\0')
         } else {
-            match cap[1].parse() {
-                Ok(x) => x,
-                Err(e) => {
-                    eprintln!("failed to parse range start: {}", &cap[1]);
-                    return Err(e);
-                }
-            }
+            Terminator::Byte(b'\n')
         };
+        let output_terminator = if opt.zero_terminated { b'\0' } else { b'\n' };
+        let output_separator = opt
+            .output_field_separator
+            .clone()
+            .unwrap_or_else(|| String::from(" "));
 
-        let end = if cap[2].is_empty() {
-            isize::max_value()
-        } else {
-            match cap[2].parse() {
-                Ok(x) => x,
-                Err(e) => {
-                    eprintln!("failed to parse range end: {}", &cap[2]);
-                    return Err(e);
-                }
+        Ok(Config {
+            opt,
+            choices,
+            split_by,
+            tokenizer,
+            max_split,
+            output_separator,
+            input_terminator,
+            output_terminator,
+        })
+    }
+
+    /// The error for `pattern`, given with `flag`, failing to build with the mistake pointed
+    /// out when regex reports where it is
+    fn regex_error(flag: &'static str, pattern: &str, error: regex::Error) -> ChooseError {
+        match diagnostic::diagnose_regex(pattern, &error) {
+            Some(diagnostic) => ChooseError::BadSeparator(flag, diagnostic),
+            None => ChooseError::BadRegex(error),
+        }
+    }
+
+    pub fn parse_choice(src: &str) -> Result<Choice, ChooseError> {
+        src.parse()
+            .map_err(|e| ChooseError::BadChoice(src.to_string(), e))
+    }
+
+    /// Parse a choice whose bounds may also be column names of `preset`
+    fn resolve_choice(src: &str, preset: Option<&Preset>) -> Result<Choice, ChooseError> {
+        let preset = match preset {
+            Some(preset) => preset,
+            None => return Config::parse_choice(src),
+        };
+        let column = |bound: &str| {
+            if bound.is_empty() || bound.parse::<isize>().is_ok() {
+                return Ok(bound.to_string());
             }
+            preset
+                .column(bound)
+                .map(|i| i.to_string())
+                .ok_or_else(|| ChooseError::UnknownColumn(bound.to_string(), preset.name))
+        };
+
+        let resolved = match src.split_once(':') {
+            Some((start, end)) => format!("{}:{}", column(start)?, column(end)?),
+            None => column(src)?,
         };
+        resolved
+            .parse()
+            .map_err(|e| ChooseError::BadChoice(src.to_string(), e))
+    }
 
-        return Ok(Choice::new(start, end));
+    /// Whether the `number`th line of the input, counting from 1, is a header or comment of the
+    /// --preset rather than a record
+    pub fn skips(&self, number: usize, line: &str) -> bool {
+        self.opt.preset.is_some_and(|p| p.skips(number, line))
     }
 }
 
@@ -103,67 +160,67 @@ mod tests {
         #[test]
         fn parse_single_choice_start() {
             let result = Config::parse_choice("6").unwrap();
-            assert_eq!(6, result.start)
+            assert_eq!(6, result.start())
         }
 
         #[test]
         fn parse_single_choice_end() {
             let result = Config::parse_choice("6").unwrap();
-            assert_eq!(6, result.end)
+            assert_eq!(6, result.end())
         }
 
         #[test]
         fn parse_none_started_range() {
             let result = Config::parse_choice(":5").unwrap();
-            assert_eq!((0, 5), (result.start, result.end))
+            assert_eq!((0, 5), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_none_terminated_range() {
             let result = Config::parse_choice("5:").unwrap();
-            assert_eq!((5, isize::max_value()), (result.start, result.end))
+            assert_eq!((5, isize::max_value()), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_full_range_pos_pos() {
             let result = Config::parse_choice("5:7").unwrap();
-            assert_eq!((5, 7), (result.start, result.end))
+            assert_eq!((5, 7), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_full_range_neg_neg() {
             let result = Config::parse_choice("-3:-1").unwrap();
-            assert_eq!((-3, -1), (result.start, result.end))
+            assert_eq!((-3, -1), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_neg_started_none_ended() {
             let result = Config::parse_choice("-3:").unwrap();
-            assert_eq!((-3, isize::max_value()), (result.start, result.end))
+            assert_eq!((-3, isize::max_value()), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_none_started_neg_ended() {
             let result = Config::parse_choice(":-1").unwrap();
-            assert_eq!((0, -1), (result.start, result.end))
+            assert_eq!((0, -1), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_full_range_pos_neg() {
             let result = Config::parse_choice("5:-3").unwrap();
-            assert_eq!((5, -3), (result.start, result.end))
+            assert_eq!((5, -3), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_full_range_neg_pos() {
             let result = Config::parse_choice("-3:5").unwrap();
-            assert_eq!((-3, 5), (result.start, result.end))
+            assert_eq!((-3, 5), (result.start(), result.end()))
         }
 
         #[test]
         fn parse_beginning_to_end_range() {
             let result = Config::parse_choice(":").unwrap();
-            assert_eq!((0, isize::max_value()), (result.start, result.end))
+            assert_eq!((0, isize::max_value()), (result.start(), result.end()))
         }
 
         #[test]
@@ -175,5 +232,222 @@ mod tests {
         fn parse_bad_range() {
             assert!(Config::parse_choice("d:i").is_err());
         }
+
+        #[test]
+        fn parse_bad_choice_error() {
+            match Config::parse_choice("d") {
+                Err(ChooseError::BadChoice(src, _)) => assert_eq!("d", src),
+                _ => panic!("expected a bad choice error"),
+            }
+        }
+
+        #[test]
+        fn parse_bad_choice_message() {
+            let message = Config::parse_choice("1-3").unwrap_err().to_string();
+            assert_eq!(
+                vec![
+                    "failed to parse choice argument 1-3",
+                    "    1-3",
+                    "     ^ unexpected `-`",
+                    "help: ranges are written with a colon: 1:3",
+                ],
+                message.lines().take(4).collect::<Vec<_>>()
+            );
+            assert!(message.ends_with(crate::choice::CHOICE_GRAMMAR));
+        }
+    }
+
+    mod config_new_tests {
+        use super::*;
+        use structopt::StructOpt;
+
+        #[test]
+        fn new_bad_field_separator() {
+            let opt = Opt::from_iter(vec!["choose", "0", "-f", "[[]"]);
+            assert!(matches!(
+                Config::new(opt),
+                Err(ChooseError::BadSeparator("--field-separator", _))
+            ));
+        }
+
+        #[test]
+        fn new_bad_record_separator() {
+            let opt = Opt::from_iter(vec!["choose", "0", "--record-separator", "("]);
+            assert!(matches!(
+                Config::new(opt),
+                Err(ChooseError::BadSeparator("--record-separator", _))
+            ));
+        }
+
+        #[test]
+        fn new_bad_separator_points_into_given_pattern() {
+            // the pattern is wrapped for paragraphs, which mustn't shift the caret
+            let opt = Opt::from_iter(vec!["choose", "0", "-p", "-f", ":("]);
+            match Config::new(opt) {
+                Err(ChooseError::BadSeparator(_, d)) => {
+                    assert_eq!((":(", 1), (d.argument.as_str(), d.column))
+                }
+                _ => panic!("expected a bad separator error"),
+            }
+        }
+
+        #[test]
+        fn new_widths() {
+            let opt = Opt::from_iter(vec!["choose", "0", "--widths", "2,*"]);
+            let config = Config::new(opt).unwrap();
+            let fields: Vec<String> = config
+                .tokenizer
+                .fields("a bc d")
+                .map(String::from)
+                .collect();
+            assert_eq!(vec!["a", "bc d"], fields);
+        }
+
+        #[test]
+        fn new_shell_words() {
+            let opt = Opt::from_iter(vec!["choose", "0", "--shell-words"]);
+            let config = Config::new(opt).unwrap();
+            let fields: Vec<String> = config
+                .tokenizer
+                .fields(r#"a "b c""#)
+                .map(String::from)
+                .collect();
+            assert_eq!(vec!["a", "b c"], fields);
+        }
+
+        #[test]
+        fn new_ignore_case() {
+            let opt = Opt::from_iter(vec!["choose", "0", "-f", "x", "--ignore-case"]);
+            let config = Config::new(opt).unwrap();
+            let fields: Vec<String> = config.tokenizer.fields("aXbxc").map(String::from).collect();
+            assert_eq!(vec!["a", "b", "c"], fields);
+        }
+
+        #[test]
+        fn new_unicode_space() {
+            let line = "a\u{a0}b\u{3000}c d";
+            let ascii = Config::new(Opt::from_iter(vec!["choose", "0"])).unwrap();
+            assert_eq!(2, ascii.tokenizer.fields(line).count());
+
+            let opt = Opt::from_iter(vec!["choose", "0", "--unicode-space"]);
+            let unicode = Config::new(opt).unwrap();
+            let fields: Vec<String> = unicode.tokenizer.fields(line).map(String::from).collect();
+            assert_eq!(vec!["a", "b", "c", "d"], fields);
+        }
+
+        #[test]
+        fn new_exclusive_range() {
+            let opt = Opt::from_iter(vec!["choose", "1:3", "-x"]);
+            let config = Config::new(opt).unwrap();
+            assert_eq!((1, 2), (config.choices[0].start(), config.choices[0].end()));
+        }
+
+        #[test]
+        fn new_exclusive_smallest_bounds() {
+            let opt = Opt::from_iter(vec!["choose", "-x", "0:-9223372036854775808"]);
+            let config = Config::new(opt).unwrap();
+            assert_eq!(isize::min_value(), config.choices[0].end());
+
+            let opt = Opt::from_iter(vec!["choose", "-x", "-9223372036854775808"]);
+            let config = Config::new(opt).unwrap();
+            assert_eq!(isize::min_value(), config.choices[0].end());
+        }
+
+        #[test]
+        fn new_zero_is_a_choice() {
+            let opt = Opt::from_iter(vec!["choose", "-0"]);
+            let config = Config::new(opt).unwrap();
+            assert!(!config.opt.null_input);
+            assert_eq!((0, 0), (config.choices[0].start(), config.choices[0].end()));
+        }
+    }
+
+    mod preset_tests {
+        use super::*;
+        use crate::fields::Fields;
+        use structopt::StructOpt;
+
+        fn config(args: Vec<&str>) -> Result<Config, ChooseError> {
+            let mut argv = vec!["choose", "--preset"];
+            argv.extend(args);
+            Config::new(Opt::from_iter(argv))
+        }
+
+        #[test]
+        fn preset_column_names() {
+            let config = config(vec!["passwd", "user", "uid:gid", "home:", "-1"]).unwrap();
+            let bounds: Vec<(isize, isize)> = config
+                .choices
+                .iter()
+                .map(|c| (c.start(), c.end()))
+                .collect();
+            assert_eq!(
+                vec![(0, 0), (2, 3), (5, isize::max_value()), (-1, -1)],
+                bounds
+            );
+        }
+
+        #[test]
+        fn preset_unknown_column() {
+            match config(vec!["passwd", "login"]) {
+                Err(ChooseError::UnknownColumn(name, preset)) => {
+                    assert_eq!(("login", "passwd"), (name.as_str(), preset))
+                }
+                _ => panic!("expected an unknown column error"),
+            }
+        }
+
+        #[test]
+        fn preset_exclusive_names() {
+            let config = config(vec!["passwd", "-x", "uid:home"]).unwrap();
+            assert_eq!((2, 4), (config.choices[0].start(), config.choices[0].end()));
+        }
+
+        #[test]
+        fn names_need_a_preset() {
+            let opt = Opt::from_iter(vec!["choose", "user"]);
+            assert!(matches!(Config::new(opt), Err(ChooseError::BadChoice(..))));
+        }
+
+        #[test]
+        fn preset_tokenizer() {
+            assert!(matches!(
+                config(vec!["nginx", "status"]).unwrap().tokenizer,
+                Tokenizer::Pattern(_)
+            ));
+        }
+
+        #[test]
+        fn preset_keeps_empty_fields() {
+            let config = config(vec!["passwd", "home"]).unwrap();
+            let line = "sshd:x:110:65534::/run/sshd:/usr/sbin/nologin";
+            let mut fields = Fields::new();
+            fields.split(line, &config.tokenizer, None);
+            assert_eq!("/run/sshd", fields.get(line, 5));
+        }
+
+        #[test]
+        fn preset_last_column_takes_the_rest() {
+            let config = config(vec!["ls-l", "name"]).unwrap();
+            let line = "-rw-r--r-- 1 alice staff 42 Jan  5 10:00 my  file.txt";
+            let mut fields = Fields::new();
+            fields.split(line, &config.tokenizer, None);
+            fields.limit_splits(config.max_split);
+            assert_eq!("my  file.txt", fields.get(line, 8));
+        }
+
+        #[test]
+        fn preset_split_by_name() {
+            let config = config(vec![
+                "passwd",
+                "user",
+                "--split-by",
+                "shell",
+                "--out-dir",
+                "out",
+            ])
+            .unwrap();
+            assert_eq!(Some(6), config.split_by.map(|c| c.start()));
+        }
     }
 }
@@ -0,0 +1,73 @@
+use std::io::{self, Write};
+
+use crate::config::Config;
+use crate::fields::{Fields, MaxSplit};
+
+/// Print the choices, after any `-x` adjustment, and how lines are split
+pub fn print_config<W: Write>(config: &Config, handle: &mut W) -> io::Result<()> {
+    for (i, choice) in config.choices.iter().enumerate() {
+        writeln!(handle, "debug: choice {}: {}", i, choice)?;
+    }
+    writeln!(handle, "debug: {}", config.tokenizer)?;
+    match config.max_split {
+        Some(MaxSplit::Left(n)) => writeln!(handle, "debug: max split: {}", n),
+        Some(MaxSplit::Right(n)) => writeln!(handle, "debug: max split: {} from the right", n),
+        None => Ok(()),
+    }
+}
+
+/// Print each field of a line with both the positive and the negative index that selects it
+pub fn print_fields<W: Write>(
+    line_number: usize,
+    line: &str,
+    fields: &Fields,
+    handle: &mut W,
+) -> io::Result<()> {
+    writeln!(handle, "debug: line {}: {:?}", line_number, line)?;
+    let len = fields.len() as isize;
+    for i in 0..fields.len() {
+        writeln!(
+            handle,
+            "debug: {:>5} {:>6}  {:?}",
+            i,
+            i as isize - len,
+            fields.get(line, i)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_config_after_exclusive() {
+        let config = Config::from_iter(vec!["choose", "-x", "1:3", "4:2", "-f", ":"]);
+        let mut out = Vec::new();
+        print_config(&config, &mut out).unwrap();
+        assert_eq!(
+            "debug: choice 0: fields 1 to 2\ndebug: choice 1: fields 3 down to 2\ndebug: separator: :\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn print_fields_with_both_indices() {
+        let config = Config::from_iter(vec!["choose", "0"]);
+        let line = "rust  is cool";
+        let mut fields = Fields::new();
+        fields.split(line, &config.tokenizer, None);
+        let mut out = Vec::new();
+        print_fields(3, line, &fields, &mut out).unwrap();
+        assert_eq!(
+            concat!(
+                "debug: line 3: \"rust  is cool\"\n",
+                "debug:     0     -3  \"rust\"\n",
+                "debug:     1     -2  \"is\"\n",
+                "debug:     2     -1  \"cool\"\n",
+            ),
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
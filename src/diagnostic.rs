@@ -0,0 +1,143 @@
+use std::fmt;
+
+/// A problem in a command line argument, shown as the argument with a caret under the character
+/// at fault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub argument: String,
+    /// Offset of the problem in `argument`, counted in characters
+    pub column: usize,
+    pub message: String,
+    /// How the argument could be fixed, if there is a likely fix
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(argument: &str, column: usize, message: impl Into<String>) -> Self {
+        Diagnostic {
+            argument: argument.to_string(),
+            column,
+            message: message.into(),
+            help: None,
+        }
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "    {}", self.argument)?;
+        write!(f, "    {}^ {}", " ".repeat(self.column), self.message)?;
+        if let Some(help) = &self.help {
+            write!(f, "\nhelp: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+/// Messages of mistakes that are usually a character meant literally, which can be escaped
+const ESCAPABLE: &[&str] = &[
+    "unclosed character class",
+    "unclosed group",
+    "unopened group",
+    "repetition operator missing expression",
+    "unclosed counted repetition",
+];
+
+/// Point at the mistake in `pattern` that `error` reports, read from the caret regex draws under
+/// the pattern. Returns `None` for errors that aren't about the syntax of a single line pattern.
+pub fn diagnose_regex(pattern: &str, error: &regex::Error) -> Option<Diagnostic> {
+    let report = match error {
+        regex::Error::Syntax(report) => report,
+        _ => return None,
+    };
+    let mut lines = report.lines().skip(1);
+    if lines.next()? != format!("    {}", pattern) {
+        return None;
+    }
+    let column = lines.next()?.strip_prefix("    ")?.find('^')?;
+    let message = lines.find_map(|line| line.strip_prefix("error: "))?;
+    let diagnostic = Diagnostic::new(pattern, column, message);
+
+    match pattern.char_indices().nth(column) {
+        Some((offset, c)) if ESCAPABLE.contains(&message) => {
+            let escaped = format!("{}\\{}", &pattern[..offset], &pattern[offset..]);
+            Some(diagnostic.help(format!("to match `{}` itself, escape it: {}", c, escaped)))
+        }
+        _ => Some(diagnostic),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_caret_under_column() {
+        let diagnostic = Diagnostic::new("1-3", 1, "unexpected `-`").help("try 1:3");
+        assert_eq!(
+            "    1-3\n     ^ unexpected `-`\nhelp: try 1:3",
+            diagnostic.to_string()
+        );
+    }
+
+    #[test]
+    fn display_without_help() {
+        let diagnostic = Diagnostic::new("x", 0, "not a number");
+        assert_eq!("    x\n    ^ not a number", diagnostic.to_string());
+    }
+
+    fn diagnose(pattern: &str) -> Option<Diagnostic> {
+        diagnose_regex(pattern, &regex::Regex::new(pattern).unwrap_err())
+    }
+
+    #[test]
+    fn diagnose_unclosed_group() {
+        let d = diagnose("a(b").unwrap();
+        assert_eq!((1, "unclosed group"), (d.column, d.message.as_str()));
+        assert_eq!(
+            Some("to match `(` itself, escape it: a\\(b"),
+            d.help.as_deref()
+        );
+    }
+
+    #[test]
+    fn diagnose_unopened_group() {
+        let d = diagnose("x)").unwrap();
+        assert_eq!(1, d.column);
+        assert!(d.help.unwrap().ends_with("x\\)"));
+    }
+
+    #[test]
+    fn diagnose_missing_repetition() {
+        let d = diagnose("*").unwrap();
+        assert_eq!(0, d.column);
+        assert!(d.help.unwrap().ends_with("\\*"));
+    }
+
+    #[test]
+    fn diagnose_counts_characters() {
+        assert_eq!(2, diagnose("éé(").unwrap().column);
+    }
+
+    #[test]
+    fn diagnose_without_help() {
+        let d = diagnose("a{2,1}").unwrap();
+        assert_eq!(None, d.help);
+    }
+
+    #[test]
+    fn diagnose_multiline_pattern() {
+        assert_eq!(None, diagnose("a\nb("));
+    }
+
+    #[test]
+    fn diagnose_too_big() {
+        let error = regex::Error::CompiledTooBig(10);
+        assert_eq!(None, diagnose_regex("a", &error));
+    }
+}
@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+
+use crate::choice;
+use crate::diagnostic::Diagnostic;
+use crate::preset::Preset;
+
+/// Everything that can stop `choose` from doing its job
+#[derive(Debug)]
+pub enum ChooseError {
+    /// A choice argument does not follow the choice grammar
+    BadChoice(String, ParseIntError),
+    /// A field or record separator is not a valid regular expression
+    BadRegex(regex::Error),
+    /// The separator regex given with a flag doesn't parse
+    BadSeparator(&'static str, Diagnostic),
+    /// The input file could not be opened
+    Open(io::Error),
+    /// The input could not be read
+    Read(io::Error),
+    /// The output could not be written
+    Write(io::Error),
+    /// The terminal used by --interactive could not be opened or controlled
+    Terminal(io::Error),
+    /// The config file could not be read or parsed
+    Settings(PathBuf, String),
+    /// A choice names a column the --preset doesn't have
+    UnknownColumn(String, &'static str),
+}
+
+impl fmt::Display for ChooseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChooseError::BadChoice(src, e) => write!(
+                f,
+                "failed to parse choice argument {}\n{}\nnote: {}",
+                src,
+                choice::diagnose(src, e),
+                choice::CHOICE_GRAMMAR
+            ),
+            ChooseError::BadRegex(regex::Error::Syntax(e)) => {
+                write!(f, "Syntax error compiling regular expression: {}", e)
+            }
+            ChooseError::BadRegex(regex::Error::CompiledTooBig(e)) => write!(
+                f,
+                "Compiled regular expression too big: compiled size cannot exceed {} bytes",
+                e
+            ),
+            ChooseError::BadRegex(e) => write!(f, "Error compiling regular expression: {}", e),
+            ChooseError::BadSeparator(flag, diagnostic) => write!(
+                f,
+                "Syntax error in the regular expression for {}\n{}",
+                flag, diagnostic
+            ),
+            ChooseError::Open(e) => write!(f, "Failed to open file: {}", e),
+            ChooseError::Read(e) => write!(f, "Failed to read input: {}", e),
+            ChooseError::Write(e) => write!(f, "Failed to write to output: {}", e),
+            ChooseError::Terminal(e) => write!(f, "Failed to use the terminal: {}", e),
+            ChooseError::Settings(path, e) => {
+                write!(f, "Error in config file {}: {}", path.display(), e)
+            }
+            ChooseError::UnknownColumn(name, preset) => {
+                let columns = Preset::find(preset).map_or(&[][..], |p| p.columns);
+                write!(
+                    f,
+                    "Unknown column {} in preset {}, expected one of {}",
+                    name,
+                    preset,
+                    columns.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl Error for ChooseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ChooseError::BadChoice(_, e) => Some(e),
+            ChooseError::BadRegex(e) => Some(e),
+            ChooseError::Open(e)
+            | ChooseError::Read(e)
+            | ChooseError::Write(e)
+            | ChooseError::Terminal(e) => Some(e),
+            ChooseError::BadSeparator(..)
+            | ChooseError::Settings(..)
+            | ChooseError::UnknownColumn(..) => None,
+        }
+    }
+}
+
+impl From<regex::Error> for ChooseError {
+    fn from(e: regex::Error) -> Self {
+        ChooseError::BadRegex(e)
+    }
+}
@@ -0,0 +1,195 @@
+//! C interface to the selection engine, built into the `cdylib` target. The matching header,
+//! `include/choose.h`, is checked against these items by `tests/ffi.rs` and regenerated with
+//! `make header`.
+
+use regex::Regex;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use crate::choice::Choice;
+use crate::fields::Fields;
+use crate::tokenizer::Tokenizer;
+
+/// Compiled choices and separator, opaque to C callers
+pub struct ChooseSelector {
+    choices: Vec<Choice>,
+    tokenizer: Tokenizer,
+    fields: Fields,
+}
+
+/// Byte offsets of a selected field within the buffer it was selected from
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChooseField {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Compile `choices`, whitespace separated choices written as on the command line, and the
+/// field `separator` regex into a selector. A null `separator` splits on whitespace.
+///
+/// Returns null if either string is not valid UTF-8, a choice does not parse or the separator
+/// is not a valid regex. The selector must be released with `choose_selector_free`.
+///
+/// # Safety
+///
+/// `choices` must be a valid NUL-terminated string and `separator` either null or a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn choose_selector_new(
+    choices: *const c_char,
+    separator: *const c_char,
+) -> *mut ChooseSelector {
+    if choices.is_null() {
+        return ptr::null_mut();
+    }
+    let choices = match CStr::from_ptr(choices).to_str() {
+        Ok(c) => c,
+        Err(_) => return ptr::null_mut(),
+    };
+    let separator = if separator.is_null() {
+        Ok("[[:space:]]")
+    } else {
+        CStr::from_ptr(separator).to_str()
+    };
+
+    let choices: Result<Vec<Choice>, _> = choices.split_whitespace().map(str::parse).collect();
+    let separator = separator.ok().and_then(|s| Regex::new(s).ok());
+
+    match (choices, separator) {
+        (Ok(choices), Some(separator)) => Box::into_raw(Box::new(ChooseSelector {
+            choices,
+            tokenizer: Tokenizer::Separator(separator),
+            fields: Fields::new(),
+        })),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Select fields from the `len` bytes of UTF-8 text at `buf`, writing the offsets of at most
+/// `capacity` of them to `fields` in the order the choices pick them.
+///
+/// Returns the number of fields selected, which may be more than `capacity` so the caller can
+/// retry with a larger array, or -1 if an argument is null or `buf` is not valid UTF-8.
+///
+/// # Safety
+///
+/// `selector` must come from `choose_selector_new` and not be used by another thread during the
+/// call, `buf` must point to `len` readable bytes and `fields` to `capacity` writable fields.
+#[no_mangle]
+pub unsafe extern "C" fn choose_selector_apply(
+    selector: *mut ChooseSelector,
+    buf: *const u8,
+    len: usize,
+    fields: *mut ChooseField,
+    capacity: usize,
+) -> isize {
+    if selector.is_null() || (buf.is_null() && len > 0) || (fields.is_null() && capacity > 0) {
+        return -1;
+    }
+    let selector = &mut *selector;
+    let line = if len == 0 {
+        ""
+    } else {
+        match std::str::from_utf8(slice::from_raw_parts(buf, len)) {
+            Ok(l) => l,
+            Err(_) => return -1,
+        }
+    };
+    let out = if capacity == 0 {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(fields, capacity)
+    };
+
+    selector.fields.split(line, &selector.tokenizer, None);
+    let mut selected = 0;
+    for choice in &selector.choices {
+        for i in choice.indices(selector.fields.len()) {
+            if let Some(field) = out.get_mut(selected) {
+                let range = selector.fields.range(i);
+                *field = ChooseField {
+                    start: range.start,
+                    end: range.end,
+                };
+            }
+            selected += 1;
+        }
+    }
+    selected as isize
+}
+
+/// Release a selector. Null is ignored.
+///
+/// # Safety
+///
+/// `selector` must be null or come from `choose_selector_new`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn choose_selector_free(selector: *mut ChooseSelector) {
+    if !selector.is_null() {
+        drop(Box::from_raw(selector));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn apply(choices: &str, separator: Option<&str>, line: &str) -> Vec<ChooseField> {
+        let choices = CString::new(choices).unwrap();
+        let separator = separator.map(|s| CString::new(s).unwrap());
+        unsafe {
+            let selector = choose_selector_new(
+                choices.as_ptr(),
+                separator.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            );
+            assert!(!selector.is_null());
+            let mut fields = vec![ChooseField::default(); 8];
+            let n = choose_selector_apply(
+                selector,
+                line.as_ptr(),
+                line.len(),
+                fields.as_mut_ptr(),
+                fields.len(),
+            );
+            choose_selector_free(selector);
+            fields.truncate(n as usize);
+            fields
+        }
+    }
+
+    #[test]
+    fn apply_returns_offsets() {
+        assert_eq!(
+            vec![
+                ChooseField { start: 12, end: 16 },
+                ChooseField { start: 0, end: 4 },
+                ChooseField { start: 6, end: 8 },
+            ],
+            apply("-1 0:1", None, "rust  is    cool")
+        );
+    }
+
+    #[test]
+    fn apply_with_separator() {
+        assert_eq!(
+            vec![ChooseField { start: 2, end: 4 }],
+            apply("1", Some(":"), "a:bc:d")
+        );
+    }
+
+    #[test]
+    fn new_rejects_bad_input() {
+        let bad_choice = CString::new("0 x").unwrap();
+        let bad_regex = CString::new("[[").unwrap();
+        let choice = CString::new("0").unwrap();
+        unsafe {
+            assert!(choose_selector_new(bad_choice.as_ptr(), ptr::null()).is_null());
+            assert!(choose_selector_new(choice.as_ptr(), bad_regex.as_ptr()).is_null());
+            assert!(choose_selector_new(ptr::null(), ptr::null()).is_null());
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use regex::Regex;
+use std::ops::Range;
+
+use crate::shell::ShellWords;
+use crate::tokenizer::{self, Tokenizer};
+
+/// A limit on how many times a line is split, leaving the rest of it as one last field on the
+/// right, or one first field on the left when splitting from the right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxSplit {
+    Left(usize),
+    Right(usize),
+}
+
+/// Byte ranges of the non-empty fields of a line, kept between lines so that splitting a line
+/// does not allocate
+#[derive(Debug, Default)]
+pub struct Fields {
+    offsets: Vec<(usize, usize)>,
+    /// The words of a line split into shell words, which the offsets point into instead of the
+    /// line since removing quotes changes them
+    words: String,
+    shell_words: bool,
+}
+
+impl Fields {
+    pub fn new() -> Self {
+        Fields::default()
+    }
+
+    /// Split `line` into fields with `tokenizer`, stopping after `limit` fields when given
+    pub fn split(&mut self, line: &str, tokenizer: &Tokenizer, limit: Option<usize>) {
+        self.offsets.clear();
+        self.shell_words = false;
+        let limit = limit.unwrap_or(usize::MAX);
+
+        match tokenizer {
+            Tokenizer::Separator(separator) => self.split_at(line, separator, Some(limit)),
+            Tokenizer::Delimiter(delimiter) => {
+                let mut start = 0;
+                for m in delimiter.find_iter(line) {
+                    if self.offsets.len() >= limit {
+                        return;
+                    }
+                    self.offsets.push((start, m.start()));
+                    start = m.end();
+                }
+                if self.offsets.len() < limit {
+                    self.offsets.push((start, line.len()));
+                }
+            }
+            Tokenizer::Pattern(pattern) => {
+                let ranges = pattern
+                    .captures_iter(line)
+                    .map(|c| tokenizer::field_range(&c))
+                    .filter(|r| !r.is_empty())
+                    .take(limit);
+                self.offsets.extend(ranges.map(|r| (r.start, r.end)));
+            }
+            Tokenizer::Widths(widths) => {
+                let ranges = widths.ranges(line).take(limit);
+                self.offsets.extend(ranges.map(|r| (r.start, r.end)));
+            }
+            Tokenizer::ShellWords => {
+                self.words.clear();
+                self.shell_words = true;
+                for word in ShellWords::new(line).take(limit) {
+                    let start = self.words.len();
+                    self.words.push_str(&word);
+                    self.offsets.push((start, self.words.len()));
+                }
+            }
+        }
+    }
+
+    /// Split `line` into the non-empty text between matches of `separator`, stopping after
+    /// `limit` fields when given
+    pub fn split_at(&mut self, line: &str, separator: &Regex, limit: Option<usize>) {
+        self.offsets.clear();
+        self.shell_words = false;
+        let limit = limit.unwrap_or(usize::MAX);
+
+        let mut start = 0;
+        for m in separator.find_iter(line) {
+            if self.offsets.len() >= limit {
+                return;
+            }
+            if m.start() > start {
+                self.offsets.push((start, m.start()));
+            }
+            start = m.end();
+        }
+
+        if start < line.len() && self.offsets.len() < limit {
+            self.offsets.push((start, line.len()));
+        }
+    }
+
+    /// Merge the fields beyond `max_split` splits into one, keeping the separators between them.
+    /// `None` leaves the fields as they are.
+    pub fn limit_splits(&mut self, max_split: Option<MaxSplit>) {
+        let len = self.offsets.len();
+        match max_split {
+            Some(MaxSplit::Left(n)) if len > n + 1 => {
+                let end = self.offsets[len - 1].1;
+                self.offsets.truncate(n + 1);
+                self.offsets[n].1 = end;
+            }
+            Some(MaxSplit::Right(n)) if len > n + 1 => {
+                let start = self.offsets[0].0;
+                self.offsets.drain(..len - n - 1);
+                self.offsets[0].0 = start;
+            }
+            _ => (),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// The `i`th field of `line`, which must be the line these fields were split from
+    pub fn get<'a>(&'a self, line: &'a str, i: usize) -> &'a str {
+        if self.shell_words {
+            &self.words[self.range(i)]
+        } else {
+            &line[self.range(i)]
+        }
+    }
+
+    /// The byte range of the `i`th field within its line, or within its unquoted words when the
+    /// line was split into shell words
+    pub fn range(&self, i: usize) -> Range<usize> {
+        let (start, end) = self.offsets[i];
+        start..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn separator(separator: &str) -> Tokenizer {
+        Tokenizer::Separator(Regex::new(separator).unwrap())
+    }
+
+    fn split(line: &str, separator: &str, limit: Option<usize>) -> Vec<String> {
+        let mut fields = Fields::new();
+        fields.split(line, &self::separator(separator), limit);
+        (0..fields.len())
+            .map(|i| fields.get(line, i).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn split_matches_regex_split() {
+        let separator = Regex::new("[[:space:]]").unwrap();
+        for line in &["", " ", "rust", "  rust  is pretty\tcool  ", "a b"] {
+            let expected: Vec<&str> = separator.split(line).filter(|s| !s.is_empty()).collect();
+            assert_eq!(expected, split(line, "[[:space:]]", None));
+        }
+    }
+
+    #[test]
+    fn split_with_limit() {
+        assert_eq!(
+            vec!["rust", "is"],
+            split("rust is pretty cool", " ", Some(2))
+        );
+    }
+
+    #[test]
+    fn split_with_limit_past_end() {
+        assert_eq!(vec!["rust", "is"], split("rust is", " ", Some(5)));
+    }
+
+    #[test]
+    fn split_reuses_buffer() {
+        let separator = separator(":");
+        let mut fields = Fields::new();
+        fields.split("a:b:c", &separator, None);
+        fields.split("d", &separator, None);
+        assert_eq!(1, fields.len());
+        assert_eq!("d", fields.get("d", 0));
+    }
+
+    #[test]
+    fn split_delimited_keeps_empty_fields() {
+        let delimiter = Tokenizer::Delimiter(Regex::new(":").unwrap());
+        let line = "sudo:x::";
+        let mut fields = Fields::new();
+        fields.split(line, &delimiter, None);
+        assert_eq!(4, fields.len());
+        assert_eq!(("x", ""), (fields.get(line, 1), fields.get(line, 3)));
+
+        fields.split(line, &delimiter, Some(3));
+        assert_eq!(3, fields.len());
+    }
+
+    #[test]
+    fn split_pattern_with_limit() {
+        let pattern = Tokenizer::Pattern(Regex::new(r#""([^"]*)"|(\S+)"#).unwrap());
+        let line = r#"a "b c" "" d"#;
+        let mut fields = Fields::new();
+        fields.split(line, &pattern, Some(2));
+        assert_eq!(2, fields.len());
+        assert_eq!("b c", fields.get(line, 1));
+    }
+
+    #[test]
+    fn split_shell_words() {
+        let line = r#"git commit -m "fix 'it'" '' a\ b"#;
+        let mut fields = Fields::new();
+        fields.split(line, &Tokenizer::ShellWords, None);
+        let words: Vec<&str> = (0..fields.len()).map(|i| fields.get(line, i)).collect();
+        assert_eq!(vec!["git", "commit", "-m", "fix 'it'", "", "a b"], words);
+
+        fields.split("a b", &separator(" "), None);
+        assert_eq!("b", fields.get("a b", 1));
+    }
+
+    mod limit_splits_tests {
+        use super::*;
+
+        fn split(line: &str, max_split: MaxSplit) -> Vec<String> {
+            let mut fields = Fields::new();
+            fields.split(line, &separator("[[:space:]]"), None);
+            fields.limit_splits(Some(max_split));
+            (0..fields.len())
+                .map(|i| fields.get(line, i).to_string())
+                .collect()
+        }
+
+        #[test]
+        fn max_split_keeps_the_rest() {
+            assert_eq!(
+                vec!["42", "/usr/bin/vim -O  a.rs b.rs"],
+                split("   42 /usr/bin/vim -O  a.rs b.rs  ", MaxSplit::Left(1))
+            );
+        }
+
+        #[test]
+        fn max_split_zero_is_one_field() {
+            assert_eq!(vec!["a  b c"], split(" a  b c ", MaxSplit::Left(0)));
+        }
+
+        #[test]
+        fn rmax_split_keeps_the_start() {
+            assert_eq!(
+                vec!["My Documents/a b", "4096"],
+                split("My Documents/a b 4096", MaxSplit::Right(1))
+            );
+        }
+
+        #[test]
+        fn max_split_beyond_fields() {
+            assert_eq!(vec!["a", "b"], split("a b", MaxSplit::Left(5)));
+            assert_eq!(vec!["a", "b"], split("a b", MaxSplit::Right(1)));
+        }
+
+        #[test]
+        fn no_max_split() {
+            let mut fields = Fields::new();
+            fields.split("a b c", &separator(" "), None);
+            fields.limit_splits(None);
+            assert_eq!(3, fields.len());
+        }
+    }
+}
@@ -0,0 +1,650 @@
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem::MaybeUninit;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::choice::Choice;
+use crate::fields::Fields;
+use crate::shell;
+use crate::tokenizer::Tokenizer;
+
+/// Number of input lines read for the picker to show
+pub(crate) const SAMPLE_LINES: usize = 50;
+
+const DEFAULT_SEPARATOR: &str = "[[:space:]]";
+const HELP: &str = "h/l or arrows: move  space: toggle  s: edit separator  enter: done  q: quit";
+
+const RESET: &str = "\x1b[0m";
+const SELECTED: &str = "\x1b[7m";
+const CURSOR: &str = "\x1b[4m";
+const ALTERNATE: &str = "\x1b[36m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Enter,
+    Backspace,
+    Esc,
+    CtrlC,
+}
+
+/// Decode the keys in a chunk of bytes read from a terminal in raw mode. Unknown escape
+/// sequences are dropped.
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                match chars.next() {
+                    Some('C') => Key::Right,
+                    Some('D') => Key::Left,
+                    _ => continue,
+                }
+            }
+            '\x1b' => Key::Esc,
+            '\r' | '\n' => Key::Enter,
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x03' => Key::CtrlC,
+            c if c.is_control() => continue,
+            c => Key::Char(c),
+        };
+        keys.push(key);
+    }
+
+    keys
+}
+
+/// What the picker wants to happen after a key press
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Continue,
+    Done(String),
+    Cancel,
+}
+
+/// State of the column picker: the sample lines, the separator they are split with and the
+/// columns chosen so far
+pub struct Picker {
+    lines: Vec<String>,
+    separator: String,
+    tokenizer: Tokenizer,
+    selected: BTreeSet<usize>,
+    cursor: usize,
+    columns: usize,
+    editing: Option<String>,
+    message: Option<String>,
+    fields: Fields,
+}
+
+impl Picker {
+    /// Start picking from `lines`, split with `separator` or whitespace, with the columns of
+    /// `choices` already selected
+    pub fn new(
+        lines: Vec<String>,
+        separator: Option<String>,
+        choices: &[Choice],
+    ) -> Result<Self, regex::Error> {
+        let separator = separator.unwrap_or_else(|| String::from(DEFAULT_SEPARATOR));
+        let tokenizer = Tokenizer::Separator(Regex::new(&separator)?);
+        let mut picker = Picker {
+            lines,
+            separator,
+            tokenizer,
+            selected: BTreeSet::new(),
+            cursor: 0,
+            columns: 0,
+            editing: None,
+            message: None,
+            fields: Fields::new(),
+        };
+        picker.count_columns();
+        for choice in choices {
+            picker.selected.extend(choice.indices(picker.columns));
+        }
+        Ok(picker)
+    }
+
+    pub fn handle(&mut self, key: Key) -> Outcome {
+        self.message = None;
+        if key == Key::CtrlC {
+            return Outcome::Cancel;
+        }
+
+        if let Some(edit) = &mut self.editing {
+            match key {
+                Key::Char(c) => edit.push(c),
+                Key::Backspace => {
+                    edit.pop();
+                }
+                Key::Enter => {
+                    let edit = edit.clone();
+                    if self.set_separator(&edit) {
+                        self.editing = None;
+                        self.separator = if edit.is_empty() {
+                            String::from(DEFAULT_SEPARATOR)
+                        } else {
+                            edit
+                        };
+                        let columns = self.columns;
+                        self.selected.retain(|&c| c < columns);
+                    }
+                    return Outcome::Continue;
+                }
+                Key::Esc => {
+                    self.editing = None;
+                    let separator = self.separator.clone();
+                    self.set_separator(&separator);
+                    return Outcome::Continue;
+                }
+                _ => return Outcome::Continue,
+            }
+            // the sample is split again on every key so the effect of an edit shows at once
+            let edit = edit.clone();
+            self.set_separator(&edit);
+            return Outcome::Continue;
+        }
+
+        match key {
+            Key::Left | Key::Char('h') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right | Key::Char('l') => {
+                self.cursor = std::cmp::min(self.cursor + 1, self.columns.saturating_sub(1))
+            }
+            Key::Char(' ') => self.toggle(),
+            Key::Char('s') | Key::Char('f') => self.editing = Some(self.separator.clone()),
+            Key::Enter if self.selected.is_empty() => {
+                self.message = Some(String::from("select at least one column first"))
+            }
+            Key::Enter => return Outcome::Done(self.command()),
+            Key::Esc | Key::Char('q') => return Outcome::Cancel,
+            _ => (),
+        }
+        Outcome::Continue
+    }
+
+    fn toggle(&mut self) {
+        if !self.selected.remove(&self.cursor) && self.cursor < self.columns {
+            self.selected.insert(self.cursor);
+        }
+    }
+
+    /// The `choose` command line printing the selected columns
+    pub fn command(&self) -> String {
+        let mut command = String::from("choose");
+        if self.separator != DEFAULT_SEPARATOR && !self.separator.is_empty() {
+            command.push_str(" -f ");
+            command.push_str(&shell::quote(&self.separator));
+        }
+
+        let mut selected = self.selected.iter().copied().peekable();
+        while let Some(start) = selected.next() {
+            let mut end = start;
+            while selected.peek() == Some(&(end + 1)) {
+                end = selected.next().unwrap();
+            }
+            if start == end {
+                command.push_str(&format!(" {}", start));
+            } else {
+                command.push_str(&format!(" {}:{}", start, end));
+            }
+        }
+        command
+    }
+
+    /// Draw the whole screen, fitting it into `width` columns and `height` rows
+    pub fn render(&mut self, width: usize, height: usize) -> String {
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        screen.push_str(&truncate(HELP, width));
+        screen.push_str("\r\n");
+
+        let status = match (&self.editing, &self.message) {
+            (Some(edit), None) => format!("separator: {}", edit),
+            (Some(edit), Some(message)) => format!("separator: {}  ({})", edit, message),
+            (None, Some(message)) => message.clone(),
+            (None, None) => format!("column {}  |  {}", self.cursor, self.command()),
+        };
+        screen.push_str(&truncate(&status, width));
+
+        for i in 0..std::cmp::min(self.lines.len(), height.saturating_sub(2)) {
+            screen.push_str("\r\n");
+            self.render_line(i, width, &mut screen);
+        }
+        screen
+    }
+
+    fn render_line(&mut self, i: usize, width: usize, screen: &mut String) {
+        let line = &self.lines[i];
+        self.fields.split(line, &self.tokenizer, None);
+
+        let mut shown = 0;
+        let mut end = 0;
+        for f in 0..self.fields.len() {
+            let range = self.fields.range(f);
+            push_visible(&line[end..range.start], "", width, &mut shown, screen);
+
+            let mut style = String::new();
+            if self.selected.contains(&f) {
+                style.push_str(SELECTED);
+            } else if f % 2 == 1 {
+                style.push_str(ALTERNATE);
+            }
+            if f == self.cursor {
+                style.push_str(CURSOR);
+            }
+            push_visible(&line[range.clone()], &style, width, &mut shown, screen);
+            end = range.end;
+        }
+        push_visible(&line[end..], "", width, &mut shown, screen);
+    }
+
+    /// Split the sample with `separator`, or whitespace when it is empty, returning false if it
+    /// isn't a valid regex
+    fn set_separator(&mut self, separator: &str) -> bool {
+        let pattern = if separator.is_empty() {
+            DEFAULT_SEPARATOR
+        } else {
+            separator
+        };
+        match Regex::new(pattern) {
+            Ok(regex) => {
+                self.tokenizer = Tokenizer::Separator(regex);
+                self.count_columns();
+                self.cursor = std::cmp::min(self.cursor, self.columns.saturating_sub(1));
+                true
+            }
+            Err(_) => {
+                self.message = Some(String::from("invalid regex"));
+                false
+            }
+        }
+    }
+
+    fn count_columns(&mut self) {
+        let mut columns = 0;
+        for line in &self.lines {
+            self.fields.split(line, &self.tokenizer, None);
+            columns = std::cmp::max(columns, self.fields.len());
+        }
+        self.columns = columns;
+    }
+}
+
+/// Append `text` in `style`, stopping once `width` characters are on the row. Control
+/// characters such as tabs are shown as spaces so they can't move the cursor.
+fn push_visible(text: &str, style: &str, width: usize, shown: &mut usize, screen: &mut String) {
+    if text.is_empty() || *shown >= width {
+        return;
+    }
+    screen.push_str(style);
+    for c in text.chars().take(width - *shown) {
+        screen.push(if c.is_control() { ' ' } else { c });
+        *shown += 1;
+    }
+    if !style.is_empty() {
+        screen.push_str(RESET);
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Puts a terminal into raw mode, restoring its previous settings when dropped
+struct RawMode {
+    fd: RawFd,
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable(fd: RawFd) -> io::Result<Self> {
+        let mut original = MaybeUninit::uninit();
+        // SAFETY: tcgetattr fully initializes the termios it is given when it succeeds
+        let original = unsafe {
+            if libc::tcgetattr(fd, original.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            original.assume_init()
+        };
+
+        let mut raw = original;
+        // SAFETY: raw is a valid termios copied from the terminal's own settings
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(RawMode { fd, original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: original holds the settings read from this terminal in enable
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Columns and rows of the terminal, falling back to 80x24 when it doesn't say
+fn window_size(fd: RawFd) -> (usize, usize) {
+    let mut size = MaybeUninit::<libc::winsize>::zeroed();
+    // SAFETY: TIOCGWINSZ writes a winsize into the zeroed buffer it is given
+    let size = unsafe {
+        if libc::ioctl(fd, libc::TIOCGWINSZ, size.as_mut_ptr()) != 0 {
+            return (80, 24);
+        }
+        size.assume_init()
+    };
+    match (size.ws_col, size.ws_row) {
+        (0, _) | (_, 0) => (80, 24),
+        (cols, rows) => (cols as usize, rows as usize),
+    }
+}
+
+/// Run the picker on the terminal `tty` until the user is done, returning the command they built
+/// or `None` if they quit
+pub fn run(tty: &File, picker: &mut Picker) -> io::Result<Option<String>> {
+    let _raw = RawMode::enable(tty.as_raw_fd())?;
+    let mut out = tty;
+    let mut input = tty;
+
+    // draw on the alternate screen with the cursor hidden, so the shell's scrollback is left
+    // as it was
+    out.write_all(b"\x1b[?1049h\x1b[?25l")?;
+    let result = (|| {
+        let mut buf = [0; 64];
+        loop {
+            let (width, height) = window_size(tty.as_raw_fd());
+            out.write_all(picker.render(width, height).as_bytes())?;
+            out.flush()?;
+
+            let n = input.read(&mut buf)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            for key in parse_keys(&buf[..n]) {
+                match picker.handle(key) {
+                    Outcome::Continue => (),
+                    Outcome::Done(command) => return Ok(Some(command)),
+                    Outcome::Cancel => return Ok(None),
+                }
+            }
+        }
+    })();
+    out.write_all(b"\x1b[?25h\x1b[?1049l")?;
+    out.flush()?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker(lines: &[&str], separator: Option<&str>) -> Picker {
+        let lines = lines.iter().map(|l| l.to_string()).collect();
+        Picker::new(lines, separator.map(String::from), &[]).unwrap()
+    }
+
+    fn press(picker: &mut Picker, keys: &str) -> Outcome {
+        let mut outcome = Outcome::Continue;
+        for key in parse_keys(keys.as_bytes()) {
+            outcome = picker.handle(key);
+        }
+        outcome
+    }
+
+    mod parse_keys_tests {
+        use super::*;
+
+        #[test]
+        fn parse_arrows_and_chars() {
+            assert_eq!(
+                vec![Key::Right, Key::Char('x'), Key::Left, Key::Enter],
+                parse_keys(b"\x1b[Cx\x1b[D\r")
+            );
+        }
+
+        #[test]
+        fn parse_lone_escape() {
+            assert_eq!(vec![Key::Esc], parse_keys(b"\x1b"));
+        }
+
+        #[test]
+        fn parse_control_keys() {
+            assert_eq!(
+                vec![Key::Backspace, Key::CtrlC],
+                parse_keys(b"\x7f\x03\x01")
+            );
+        }
+
+        #[test]
+        fn parse_drops_unknown_sequences() {
+            assert_eq!(vec![Key::Char('a')], parse_keys(b"\x1b[Aa"));
+        }
+    }
+
+    mod picker_tests {
+        use super::*;
+
+        #[test]
+        fn toggle_columns() {
+            let mut p = picker(&["a b c d", "e f"], None);
+            assert_eq!(Outcome::Continue, press(&mut p, " ll ll "));
+            assert_eq!("choose 0 2:3", p.command());
+        }
+
+        #[test]
+        fn toggle_off() {
+            let mut p = picker(&["a b c"], None);
+            press(&mut p, " l  ");
+            assert_eq!("choose 0", p.command());
+        }
+
+        #[test]
+        fn cursor_stays_on_columns() {
+            let mut p = picker(&["a b"], None);
+            press(&mut p, "hhlllll ");
+            assert_eq!("choose 1", p.command());
+        }
+
+        #[test]
+        fn enter_prints_command() {
+            let mut p = picker(&["a b"], None);
+            assert_eq!(
+                Outcome::Done(String::from("choose 1")),
+                press(&mut p, "l \r")
+            );
+        }
+
+        #[test]
+        fn enter_needs_a_selection() {
+            let mut p = picker(&["a b"], None);
+            assert_eq!(Outcome::Continue, press(&mut p, "\r"));
+        }
+
+        #[test]
+        fn quit() {
+            let mut p = picker(&["a b"], None);
+            assert_eq!(Outcome::Cancel, press(&mut p, " q"));
+        }
+
+        #[test]
+        fn preselect_choices() {
+            let lines = vec![String::from("a b c d")];
+            let choices = vec!["1:2".parse().unwrap(), "-1".parse().unwrap()];
+            let p = Picker::new(lines, None, &choices).unwrap();
+            assert_eq!("choose 1:3", p.command());
+        }
+
+        #[test]
+        fn edit_separator() {
+            let mut p = picker(&["a:b c:d"], None);
+            press(
+                &mut p,
+                "s\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f:\rll ",
+            );
+            assert_eq!("choose -f : 2", p.command());
+        }
+
+        #[test]
+        fn edit_separator_applies_live() {
+            let mut p = picker(&["a:b:c"], None);
+            press(&mut p, "s\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f:");
+            assert_eq!(3, p.columns);
+        }
+
+        #[test]
+        fn edit_separator_escape_reverts() {
+            let mut p = picker(&["a:b:c d"], Some(":"));
+            press(&mut p, "s\x7f ");
+            assert_eq!(2, p.columns);
+            press(&mut p, "\x1b");
+            assert_eq!(3, p.columns);
+            assert_eq!("choose -f :", p.command());
+        }
+
+        #[test]
+        fn edit_separator_drops_missing_columns() {
+            let mut p = picker(&["a b c"], None);
+            press(
+                &mut p,
+                "ll s\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f:\r",
+            );
+            assert_eq!("choose -f :", p.command());
+        }
+
+        #[test]
+        fn invalid_separator_keeps_last_valid_one() {
+            let mut p = picker(&["a:b c"], Some(":"));
+            press(&mut p, "s[");
+            assert_eq!(2, p.columns);
+            assert!(p.render(80, 24).contains("separator: :[  (invalid regex)"));
+            press(&mut p, "\r");
+            assert!(p.editing.is_some());
+        }
+
+        #[test]
+        fn quote_separator() {
+            let p = picker(&["a"], Some("'"));
+            assert_eq!(r"choose -f ''\'''", p.command());
+        }
+
+        #[test]
+        fn render_highlights_columns() {
+            let mut p = picker(&["a b c"], None);
+            press(&mut p, "l ");
+            let screen = p.render(80, 24);
+            let line = screen.rsplit("\r\n").next().unwrap();
+            assert_eq!("a \x1b[7m\x1b[4mb\x1b[0m c", line);
+        }
+
+        #[test]
+        fn render_fits_the_terminal() {
+            let mut p = picker(&["a\tbcdef", "second", "third"], None);
+            let screen = p.render(3, 3);
+            let rows: Vec<&str> = screen.split("\r\n").collect();
+            assert_eq!(3, rows.len());
+            assert_eq!("\x1b[4ma\x1b[0m \x1b[36mb\x1b[0m", rows[2]);
+        }
+    }
+
+    mod pty_tests {
+        use super::*;
+        use std::os::unix::io::FromRawFd;
+        use std::ptr;
+        use std::sync::mpsc;
+        use std::thread;
+        use std::time::Duration;
+
+        /// Open a pseudo terminal, returning its controlling and its terminal side
+        fn open_pty() -> (File, File) {
+            let mut master = 0;
+            let mut slave = 0;
+            let size = libc::winsize {
+                ws_row: 24,
+                ws_col: 80,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            // SAFETY: openpty writes two new file descriptors, which are then owned by the
+            // returned files
+            unsafe {
+                assert_eq!(
+                    0,
+                    libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), &size)
+                );
+                (File::from_raw_fd(master), File::from_raw_fd(slave))
+            }
+        }
+
+        /// Drive the picker through a pty with `keys`, returning its result and everything it
+        /// drew
+        fn drive(lines: &[&str], keys: &[u8]) -> (Option<String>, String) {
+            let (mut master, slave) = open_pty();
+            let mut reader = master.try_clone().unwrap();
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || {
+                let mut buf = [0; 4096];
+                while let Ok(n) = reader.read(&mut buf) {
+                    if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut p = picker(lines, None);
+            let ui = thread::spawn(move || run(&slave, &mut p).unwrap());
+
+            // only type once the first screen is drawn, so raw mode is on
+            let mut drawn = Vec::new();
+            while !String::from_utf8_lossy(&drawn).contains(HELP) {
+                drawn.extend(rx.recv_timeout(Duration::from_secs(5)).unwrap());
+            }
+            master.write_all(keys).unwrap();
+            let result = ui.join().unwrap();
+
+            while let Ok(chunk) = rx.recv_timeout(Duration::from_millis(200)) {
+                drawn.extend(chunk);
+            }
+            (result, String::from_utf8_lossy(&drawn).into_owned())
+        }
+
+        #[test]
+        fn pty_pick_columns() {
+            let (result, drawn) = drive(&["rust is cool", "so is this"], b"l \x1b[C \r");
+            assert_eq!(Some(String::from("choose 1:2")), result);
+            assert!(drawn.contains("\x1b[?1049h"));
+            assert!(drawn.ends_with("\x1b[?25h\x1b[?1049l"));
+        }
+
+        #[test]
+        fn pty_quit() {
+            let (result, _) = drive(&["rust is cool"], b" \x03");
+            assert_eq!(None, result);
+        }
+
+        #[test]
+        fn pty_restores_terminal() {
+            let (_master, slave) = open_pty();
+            let mut before = MaybeUninit::uninit();
+            let mut after = MaybeUninit::uninit();
+            // SAFETY: tcgetattr initializes the termios it is given on success
+            unsafe {
+                assert_eq!(0, libc::tcgetattr(slave.as_raw_fd(), before.as_mut_ptr()));
+                drop(RawMode::enable(slave.as_raw_fd()).unwrap());
+                assert_eq!(0, libc::tcgetattr(slave.as_raw_fd(), after.as_mut_ptr()));
+                assert_eq!(before.assume_init().c_lflag, after.assume_init().c_lflag);
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! The field selection engine behind the `choose` command line tool.
+//!
+//! A line is split into fields at matches of a separator regex, skipping empty fields, and each
+//! [`Choice`] selects a single field or a range of them using the same syntax as the command
+//! line:
+//!
+//! ```
+//! use choose::{parse_choice, select};
+//! use regex::Regex;
+//!
+//! let choices = vec![parse_choice("-1").unwrap(), parse_choice("0:1").unwrap()];
+//! let separator = Regex::new("[[:space:]]").unwrap();
+//!
+//! let fields: Vec<&str> = select("rust is pretty cool", &choices, &separator).collect();
+//! assert_eq!(vec!["cool", "rust", "is"], fields);
+//! ```
+
+#![allow(clippy::legacy_numeric_constants)]
+
+#[macro_use]
+extern crate lazy_static;
+
+use regex::Regex;
+use std::num::ParseIntError;
+
+mod choice;
+#[doc(hidden)]
+pub mod cli;
+mod config;
+mod debug;
+mod diagnostic;
+mod error;
+mod ffi;
+mod fields;
+#[cfg(unix)]
+mod interactive;
+mod man;
+mod opt;
+mod preset;
+mod preview;
+mod reader;
+mod settings;
+mod shell;
+mod tokenizer;
+mod writer;
+
+pub use choice::{Choice, Indices};
+pub use diagnostic::Diagnostic;
+pub use error::ChooseError;
+
+use fields::Fields;
+
+/// Parse a choice written as `x`, `x:`, `:y` or `x:y`, where `x` and `y` are integers, negative
+/// ones counting back from the end of the line
+pub fn parse_choice(src: &str) -> Result<Choice, ParseIntError> {
+    src.parse()
+}
+
+/// The fields of `line` picked by each of `choices` in turn, splitting the line at matches of
+/// `separator` only once
+pub fn select<'a>(line: &'a str, choices: &'a [Choice], separator: &Regex) -> Select<'a> {
+    let mut fields = Fields::new();
+    fields.split_at(line, separator, None);
+
+    Select {
+        line,
+        fields,
+        choices: choices.iter(),
+        indices: None,
+    }
+}
+
+/// Iterator returned by [`select`]
+#[derive(Debug)]
+pub struct Select<'a> {
+    line: &'a str,
+    fields: Fields,
+    choices: std::slice::Iter<'a, Choice>,
+    indices: Option<Indices>,
+}
+
+impl<'a> Iterator for Select<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            if let Some(i) = self.indices.as_mut().and_then(Iterator::next) {
+                return Some(&self.line[self.fields.range(i)]);
+            }
+            self.indices = Some(self.choices.next()?.indices(self.fields.len()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select_all(line: &str, choices: &[&str], separator: &str) -> Vec<String> {
+        let choices: Vec<Choice> = choices.iter().map(|c| parse_choice(c).unwrap()).collect();
+        let separator = Regex::new(separator).unwrap();
+        select(line, &choices, &separator)
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn select_single_field() {
+        assert_eq!(vec!["is"], select_all("rust is cool", &["1"], " "));
+    }
+
+    #[test]
+    fn select_several_choices_in_order() {
+        assert_eq!(
+            vec!["cool", "rust", "is"],
+            select_all("rust is cool", &["2", "0:1"], " ")
+        );
+    }
+
+    #[test]
+    fn select_reversed_range() {
+        assert_eq!(
+            vec!["cool", "is"],
+            select_all("rust#is##cool", &["2:1"], "#")
+        );
+    }
+
+    #[test]
+    fn select_out_of_range() {
+        assert!(select_all("rust", &["3", "-5:-3"], " ").is_empty());
+    }
+
+    #[test]
+    fn select_no_choices() {
+        assert!(select_all("rust is cool", &[], " ").is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use std::io::{self, Write};
+use structopt::clap::App;
+
+/// One entry of a section of the help text, like `-f, --field-separator <field-separator>`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub names: Vec<String>,
+    pub value: Option<String>,
+    pub help: String,
+}
+
+/// A titled section of the help text, like `FLAGS` or `OPTIONS`
+#[derive(Debug)]
+pub struct Section {
+    pub title: String,
+    pub entries: Vec<Entry>,
+}
+
+/// The help clap prints for an app, read back from its unwrapped text so that the man page
+/// always documents the same arguments as `--help`
+#[derive(Debug)]
+pub struct Help {
+    pub version: String,
+    pub about: String,
+    pub usage: Vec<String>,
+    pub sections: Vec<Section>,
+}
+
+pub fn read_help(app: App) -> io::Result<Help> {
+    let mut text = Vec::new();
+    app.set_term_width(0)
+        .write_help(&mut text)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let text = String::from_utf8_lossy(&text);
+    let mut lines = text.lines();
+
+    let version = lines.next().unwrap_or_default().to_string();
+    let about = lines.next().unwrap_or_default().to_string();
+    let mut usage = Vec::new();
+    let mut sections: Vec<Section> = Vec::new();
+    for line in lines {
+        if !line.starts_with(' ') && line.ends_with(':') {
+            sections.push(Section {
+                title: line.trim_end_matches(':').to_string(),
+                entries: Vec::new(),
+            });
+            continue;
+        }
+        let section = match sections.last_mut() {
+            _ if line.trim().is_empty() => continue,
+            Some(section) if section.title == "USAGE" => {
+                usage.push(line.trim().to_string());
+                continue;
+            }
+            Some(section) => section,
+            None => continue,
+        };
+
+        let line = line.trim();
+        let (usage, help) = match line.find("  ") {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        let mut words = usage.split_whitespace().map(|w| w.trim_end_matches(','));
+        let mut entry = Entry {
+            names: Vec::new(),
+            value: None,
+            help: help.to_string(),
+        };
+        for word in &mut words {
+            if word.starts_with('<') && section.title != "ARGS" {
+                entry.value = Some(word.to_string());
+            } else {
+                entry.names.push(word.to_string());
+            }
+        }
+        section.entries.push(entry);
+    }
+
+    Ok(Help {
+        version,
+        about,
+        usage,
+        sections,
+    })
+}
+
+/// Write a man page for `app` in roff format
+pub fn write_man<W: Write>(app: App, handle: &mut W) -> io::Result<()> {
+    let name = app.get_name().to_string();
+    let help = read_help(app)?;
+
+    writeln!(
+        handle,
+        ".TH {} 1 \"\" \"{}\" \"User Commands\"",
+        name.to_uppercase(),
+        help.version
+    )?;
+    writeln!(handle, ".SH NAME")?;
+    writeln!(handle, "{} \\- {}", name, escape(&help.about))?;
+    writeln!(handle, ".SH SYNOPSIS")?;
+    for (i, usage) in help.usage.iter().enumerate() {
+        if i > 0 {
+            writeln!(handle, ".br")?;
+        }
+        let args = usage.trim_start_matches(name.as_str()).trim();
+        writeln!(handle, ".B {}\n{}", name, escape(args))?;
+    }
+
+    for section in &help.sections {
+        if section.title == "USAGE" {
+            continue;
+        }
+        writeln!(handle, ".SH {}", section.title)?;
+        for entry in &section.entries {
+            let names: Vec<String> = entry
+                .names
+                .iter()
+                .map(|n| format!("\\fB{}\\fR", escape(n)))
+                .collect();
+            writeln!(handle, ".TP")?;
+            match &entry.value {
+                Some(value) => writeln!(handle, "{} \\fI{}\\fR", names.join(", "), escape(value))?,
+                None => writeln!(handle, "{}", names.join(", "))?,
+            }
+            writeln!(handle, "{}", escape(&entry.help))?;
+        }
+    }
+
+    writeln!(handle, ".SH EXIT STATUS")?;
+    for (code, meaning) in &[
+        (0, "Success."),
+        (1, "Invalid arguments, choices or config file."),
+        (2, "Invalid field or record separator regex."),
+        (3, "The input file or terminal could not be opened or read."),
+        (4, "The output could not be written."),
+    ] {
+        writeln!(handle, ".TP\n{}\n{}", code, meaning)?;
+    }
+    Ok(())
+}
+
+/// Escape text for roff, so dashes stay dashes and no line starts a request
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opt::Opt;
+    use structopt::clap::Shell;
+    use structopt::StructOpt;
+
+    /// Every long flag and option defined in Opt, like `--debug`
+    fn long_flags() -> Vec<String> {
+        let flags: Vec<String> = read_help(Opt::clap())
+            .unwrap()
+            .sections
+            .into_iter()
+            .filter(|s| s.title == "FLAGS" || s.title == "OPTIONS")
+            .flat_map(|s| s.entries)
+            .flat_map(|e| e.names)
+            .filter(|n| n.starts_with("--"))
+            .collect();
+        // make sure reading the help back works, so the tests below aren't vacuous
+        assert!(flags.contains(&String::from("--field-separator")));
+        assert!(flags.contains(&String::from("--debug")));
+        flags
+    }
+
+    fn completions(shell: Shell) -> String {
+        let mut script = Vec::new();
+        Opt::clap().gen_completions_to("choose", shell, &mut script);
+        String::from_utf8(script).unwrap()
+    }
+
+    #[test]
+    fn read_help_version_and_about() {
+        let help = read_help(Opt::clap()).unwrap();
+        assert!(help.version.starts_with("choose "));
+        assert_eq!("`choose` sections from each line of files", help.about);
+    }
+
+    #[test]
+    fn help_sections_read_options() {
+        let help = read_help(Opt::clap()).unwrap();
+        let options = help.sections.iter().find(|s| s.title == "OPTIONS").unwrap();
+        assert!(options.entries.contains(&Entry {
+            names: vec![String::from("-f"), String::from("--field-separator")],
+            value: Some(String::from("<field-separator>")),
+            help: String::from(
+                "Specify field separator other than whitespace, using Rust `regex` syntax"
+            ),
+        }));
+    }
+
+    #[test]
+    fn help_sections_read_args() {
+        let help = read_help(Opt::clap()).unwrap();
+        let args = help.sections.iter().find(|s| s.title == "ARGS").unwrap();
+        assert_eq!(vec![String::from("<choice>...")], args.entries[0].names);
+    }
+
+    #[test]
+    fn bash_completions_mention_every_flag() {
+        let script = completions(Shell::Bash);
+        for flag in long_flags() {
+            assert!(script.contains(&flag), "{} missing", flag);
+        }
+    }
+
+    #[test]
+    fn zsh_completions_mention_every_flag() {
+        let script = completions(Shell::Zsh);
+        for flag in long_flags() {
+            assert!(script.contains(&flag), "{} missing", flag);
+        }
+    }
+
+    #[test]
+    fn fish_completions_mention_every_flag() {
+        let script = completions(Shell::Fish);
+        for flag in long_flags() {
+            // fish lists long flags without their dashes
+            assert!(
+                script.contains(&format!("-l {}", &flag[2..])),
+                "{} missing",
+                flag
+            );
+        }
+    }
+
+    #[test]
+    fn man_page_mentions_every_flag() {
+        let mut page = Vec::new();
+        write_man(Opt::clap(), &mut page).unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.starts_with(".TH CHOOSE 1"));
+        for flag in long_flags() {
+            assert!(page.contains(&escape(&flag)), "{} missing", flag);
+        }
+    }
+
+    #[test]
+    fn escape_roff() {
+        assert_eq!("\\-\\-debug", escape("--debug"));
+        assert_eq!("\\&.hidden", escape(".hidden"));
+        assert_eq!("a\\ez", escape("a\\z"));
+    }
+}
@@ -1,32 +1,180 @@
 use std::path::PathBuf;
+use structopt::clap::Shell;
 use structopt::StructOpt;
 
-use crate::choice::Choice;
-use crate::config::Config;
+use crate::preset::Preset;
+use crate::shell::QuoteStyle;
+use crate::tokenizer::Widths;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "choose", about = "`choose` sections from each line of files")]
 #[structopt(setting = structopt::clap::AppSettings::AllowLeadingHyphen)]
+#[structopt(setting = structopt::clap::AppSettings::ArgsNegateSubcommands)]
+#[structopt(setting = structopt::clap::AppSettings::SubcommandsNegateReqs)]
 pub struct Opt {
     /// Specify field separator other than whitespace, using Rust `regex` syntax
     #[structopt(short, long)]
     pub field_separator: Option<String>,
 
+    /// Match the field and record separators case-insensitively
+    #[structopt(long)]
+    pub ignore_case: bool,
+
+    /// Split on Unicode whitespace, like no-break and ideographic spaces, instead of only ASCII
+    /// whitespace
+    #[structopt(long, conflicts_with_all = &["field-separator", "preset"])]
+    pub unicode_space: bool,
+
+    /// Split lines into columns of fixed widths in characters, like 8,12,5,*, where a final *
+    /// takes the rest of the line. Padding around each column is trimmed and blank columns keep
+    /// their index.
+    #[structopt(
+        long,
+        conflicts_with_all = &["field-separator", "preset", "unicode-space", "interactive"]
+    )]
+    pub widths: Option<Widths>,
+
+    /// Split lines into words like a POSIX shell, honoring single and double quotes and
+    /// backslash escapes, which are removed from the fields. A word starting with # comments out
+    /// the rest of the line.
+    #[structopt(
+        long,
+        conflicts_with_all = &[
+            "field-separator", "preset", "widths", "auto-columns", "unicode-space", "max-split",
+            "rmax-split", "interactive"
+        ]
+    )]
+    pub shell_words: bool,
+
+    /// Find the columns of text aligned with spaces, like the output of ps aux, docker ps or
+    /// kubectl get, from where its first N lines, starting with a header, are all blank
+    #[structopt(
+        long,
+        value_name = "N",
+        conflicts_with_all = &[
+            "field-separator", "preset", "widths", "unicode-space", "interactive", "paragraph",
+            "record-separator"
+        ]
+    )]
+    pub auto_columns: Option<usize>,
+
+    /// Split each line at most N times, so the last field is the rest of the line with its
+    /// separators kept
+    #[structopt(long, value_name = "N", conflicts_with_all = &["rmax-split", "interactive"])]
+    pub max_split: Option<usize>,
+
+    /// Split each line at most N times counting from the right, so the first field is the start
+    /// of the line with its separators kept
+    #[structopt(long, value_name = "N", conflicts_with = "interactive")]
+    pub rmax_split: Option<usize>,
+
+    /// Separator printed after each chosen field instead of a space
+    #[structopt(short, long)]
+    pub output_field_separator: Option<String>,
+
+    /// Quote the printed fields, with shell quoting so they can be read back by eval or xargs
+    #[structopt(long, value_name = "STYLE", possible_values = &["shell"])]
+    pub quote_output: Option<QuoteStyle>,
+
     /// Use exclusive ranges, similar to array indexing in many programming languages
     #[structopt(short = "x", long)]
     pub exclusive: bool,
 
-    /// Activate debug mode
+    /// Print the parsed choices, the separator and the numbered fields of each line to stderr
     #[structopt(short, long)]
     pub debug: bool,
 
+    /// Flush output after every line, even when not writing to a terminal
+    #[structopt(long)]
+    pub line_buffered: bool,
+
+    /// Input and output records are terminated by NUL instead of newline
+    #[structopt(short, long)]
+    pub zero_terminated: bool,
+
+    /// Input records are terminated by NUL instead of newline, as produced by `find -print0`
+    #[structopt(long)]
+    pub null_input: bool,
+
+    /// Split input into records at matches of this regex instead of at each newline, using Rust
+    /// `regex` syntax
+    #[structopt(long, conflicts_with_all = &["zero-terminated", "null-input"])]
+    pub record_separator: Option<String>,
+
+    /// Treat each block of lines separated by blank lines as one record
+    #[structopt(
+        short,
+        long,
+        conflicts_with_all = &["record-separator", "zero-terminated", "null-input"]
+    )]
+    pub paragraph: bool,
+
     /// Input file
     #[structopt(short, long, parse(from_os_str))]
     pub input: Option<PathBuf>,
 
-    /// Fields to print. Either x, x:, :y, or x:y, where x and y are integers, colons indicate a
-    /// range, and an empty field on either side of the colon continues to the beginning or end of
-    /// the line.
-    #[structopt(required = true, min_values = 1, parse(try_from_str = Config::parse_choice))]
-    pub choice: Vec<Choice>,
+    /// Write each line to a file in --out-dir named after this field, instead of to stdout
+    #[structopt(long, requires = "out-dir")]
+    pub split_by: Option<String>,
+
+    /// Directory to write files into when using --split-by
+    #[structopt(long, requires = "split-by", parse(from_os_str))]
+    pub out_dir: Option<PathBuf>,
+
+    /// Maximum number of files kept open at once when using --split-by
+    #[structopt(long, default_value = "64")]
+    pub max_open_files: usize,
+
+    /// Print the first N lines with each field labelled by its index instead of choosing fields
+    #[structopt(long, value_name = "N", conflicts_with = "split-by")]
+    pub preview: Option<usize>,
+
+    /// Show the --preview as a table of columns headed by their index
+    #[structopt(long, requires = "preview")]
+    pub table: bool,
+
+    /// Pick columns from a sample of the input in the terminal, then print the matching `choose`
+    /// command
+    #[structopt(
+        long,
+        conflicts_with_all = &["preview", "split-by", "paragraph", "record-separator"]
+    )]
+    pub interactive: bool,
+
+    /// Print a man page in roff format
+    #[structopt(long)]
+    pub man: bool,
+
+    /// Print the effective settings, each with where it came from: the command line, an
+    /// environment variable, the config file or the built-in default
+    #[structopt(long)]
+    pub print_config: bool,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
+    /// Split lines the way a common file or command output is laid out, naming its columns:
+    /// passwd, group, fstab, ps (aux), df, ls-l, nginx or apache (combined access logs)
+    #[structopt(
+        long,
+        parse(try_from_str = Preset::find),
+        conflicts_with_all = &["field-separator", "paragraph", "record-separator"]
+    )]
+    pub preset: Option<&'static Preset>,
+
+    /// Fields to print. Either x, x:, :y, or x:y, where x and y are integers or column names of
+    /// a --preset, colons indicate a range, and an empty field on either side of the colon
+    /// continues to the beginning or end of the line.
+    #[structopt(required_unless_one = &["preview", "interactive", "man", "print-config"], min_values = 1)]
+    pub choice: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Print a completion script for a shell
+    Completions {
+        /// The shell to complete for
+        #[structopt(possible_values = &["bash", "zsh", "fish"])]
+        shell: Shell,
+    },
 }
@@ -0,0 +1,206 @@
+use std::fmt;
+
+/// Matches the fields of a combined format access log line: bracketed and quoted fields count as
+/// one field each, without their brackets or quotes
+const ACCESS_LOG_PATTERN: &str = r#"\[([^\]]*)\]|"((?:[^"\\]|\\.)*)"|(\S+)"#;
+
+const ACCESS_LOG_COLUMNS: &[&str] = &[
+    "addr", "ident", "user", "time", "request", "status", "bytes", "referer", "agent",
+];
+
+/// The layout of a common file or command output: how to split its lines, which lines aren't
+/// records and what its columns are called
+#[derive(Debug, PartialEq, Eq)]
+pub struct Preset {
+    pub name: &'static str,
+    /// Regex matching the separators between fields, or the fields themselves when `pattern` is
+    /// set
+    pub regex: &'static str,
+    pub pattern: bool,
+    /// Empty fields between separators are kept, as in delimiter-separated files
+    pub keep_empty: bool,
+    /// Lines are split at most this many times, so the last column, which may contain
+    /// separators, takes the rest of the line
+    pub max_split: Option<usize>,
+    /// Number of header lines before the first record
+    pub header_lines: usize,
+    /// Lines starting with this are skipped
+    pub comment: Option<&'static str>,
+    pub columns: &'static [&'static str],
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "passwd",
+        regex: ":",
+        pattern: false,
+        keep_empty: true,
+        max_split: None,
+        header_lines: 0,
+        comment: Some("#"),
+        columns: &["user", "password", "uid", "gid", "gecos", "home", "shell"],
+    },
+    Preset {
+        name: "group",
+        regex: ":",
+        pattern: false,
+        keep_empty: true,
+        max_split: None,
+        header_lines: 0,
+        comment: Some("#"),
+        columns: &["group", "password", "gid", "members"],
+    },
+    Preset {
+        name: "fstab",
+        regex: "[[:space:]]",
+        pattern: false,
+        keep_empty: false,
+        max_split: None,
+        header_lines: 0,
+        comment: Some("#"),
+        columns: &["device", "mountpoint", "type", "options", "dump", "pass"],
+    },
+    Preset {
+        name: "ps",
+        regex: "[[:space:]]",
+        pattern: false,
+        keep_empty: false,
+        max_split: Some(10),
+        header_lines: 1,
+        comment: None,
+        columns: &[
+            "user", "pid", "cpu", "mem", "vsz", "rss", "tty", "stat", "start", "time", "command",
+        ],
+    },
+    Preset {
+        name: "df",
+        regex: "[[:space:]]",
+        pattern: false,
+        keep_empty: false,
+        max_split: None,
+        header_lines: 1,
+        comment: None,
+        columns: &["filesystem", "size", "used", "avail", "use", "mounted"],
+    },
+    Preset {
+        name: "ls-l",
+        regex: "[[:space:]]",
+        pattern: false,
+        keep_empty: false,
+        max_split: Some(8),
+        header_lines: 0,
+        comment: Some("total "),
+        columns: &[
+            "mode", "links", "owner", "group", "size", "month", "day", "time", "name",
+        ],
+    },
+    Preset {
+        name: "nginx",
+        regex: ACCESS_LOG_PATTERN,
+        pattern: true,
+        keep_empty: false,
+        max_split: None,
+        header_lines: 0,
+        comment: None,
+        columns: ACCESS_LOG_COLUMNS,
+    },
+    Preset {
+        name: "apache",
+        regex: ACCESS_LOG_PATTERN,
+        pattern: true,
+        keep_empty: false,
+        max_split: None,
+        header_lines: 0,
+        comment: None,
+        columns: ACCESS_LOG_COLUMNS,
+    },
+];
+
+impl Preset {
+    /// Look up a preset by name
+    pub fn find(name: &str) -> Result<&'static Preset, UnknownPreset> {
+        PRESETS
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| UnknownPreset(name.to_string()))
+    }
+
+    /// The index of the column called `name`
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|&c| c == name)
+    }
+
+    /// Whether `line`, the `number`th line of the input counting from 1, is a header or comment
+    /// rather than a record
+    pub fn skips(&self, number: usize, line: &str) -> bool {
+        number <= self.header_lines || self.comment.is_some_and(|c| line.starts_with(c))
+    }
+}
+
+/// Error for a --preset name that isn't one of [`PRESETS`]
+#[derive(Debug)]
+pub struct UnknownPreset(String);
+
+impl fmt::Display for UnknownPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+        write!(
+            f,
+            "unknown preset {}, expected one of {}",
+            self.0,
+            names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownPreset {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn presets_compile() {
+        for preset in PRESETS {
+            assert!(Regex::new(preset.regex).is_ok(), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn column_names_are_unique_and_not_numbers() {
+        for preset in PRESETS {
+            for (i, column) in preset.columns.iter().enumerate() {
+                assert_eq!(Some(i), preset.column(column), "{}", preset.name);
+                assert!(column.parse::<isize>().is_err());
+                assert!(!column.contains(':'));
+            }
+        }
+    }
+
+    #[test]
+    fn max_split_leaves_the_last_column_whole() {
+        for preset in PRESETS.iter().filter(|p| p.max_split.is_some()) {
+            assert_eq!(Some(preset.columns.len() - 1), preset.max_split);
+        }
+    }
+
+    #[test]
+    fn find_unknown() {
+        let e = Preset::find("csv").unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("unknown preset csv, expected one of passwd, group"));
+    }
+
+    #[test]
+    fn skips_headers_and_comments() {
+        let ps = Preset::find("ps").unwrap();
+        assert!(ps.skips(1, "USER PID"));
+        assert!(!ps.skips(2, "root 1"));
+
+        let fstab = Preset::find("fstab").unwrap();
+        assert!(fstab.skips(3, "# <file system>"));
+        assert!(!fstab.skips(1, "/dev/sda1 / ext4"));
+    }
+}
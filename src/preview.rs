@@ -0,0 +1,107 @@
+use std::io::{self, Write};
+
+use crate::config::Config;
+use crate::fields::Fields;
+
+/// Print each line with every field prefixed by its index, e.g. `0:root 1:x 2:0`
+pub fn print_inline<W: Write>(config: &Config, lines: &[String], handle: &mut W) -> io::Result<()> {
+    let mut fields = Fields::new();
+    for line in lines {
+        fields.split(line, &config.tokenizer, None);
+        fields.limit_splits(config.max_split);
+        for i in 0..fields.len() {
+            if i > 0 {
+                handle.write_all(b" ")?;
+            }
+            write!(handle, "{}:{}", i, fields.get(line, i))?;
+        }
+        handle.write_all(&[config.output_terminator])?;
+    }
+    Ok(())
+}
+
+/// Print the lines as a table with one column per field index, headed by that index
+pub fn print_table<W: Write>(config: &Config, lines: &[String], handle: &mut W) -> io::Result<()> {
+    let mut fields = Fields::new();
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .map(|line| {
+            fields.split(line, &config.tokenizer, None);
+            fields.limit_splits(config.max_split);
+            (0..fields.len())
+                .map(|i| fields.get(line, i).to_string())
+                .collect()
+        })
+        .collect();
+
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let header: Vec<String> = (0..columns).map(|i| i.to_string()).collect();
+    let mut widths: Vec<usize> = header.iter().map(String::len).collect();
+    for row in &rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = std::cmp::max(*width, field.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        write_row(row, &widths, handle)?;
+        handle.write_all(&[config.output_terminator])?;
+    }
+    Ok(())
+}
+
+fn write_row<W: Write>(row: &[String], widths: &[usize], handle: &mut W) -> io::Result<()> {
+    for (i, field) in row.iter().enumerate() {
+        if i + 1 == row.len() {
+            // no padding after the last field of a row
+            handle.write_all(field.as_bytes())?;
+        } else {
+            write!(handle, "{:<width$}  ", field, width = widths[i])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn inline_labels_fields() {
+        let config = Config::from_iter(vec!["choose", "--preview", "2", "-f", ":"]);
+        let mut out = Vec::new();
+        print_inline(&config, &lines(&["root:x:0", "a::b"]), &mut out).unwrap();
+        assert_eq!("0:root 1:x 2:0\n0:a 1:b\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn inline_empty_line() {
+        let config = Config::from_iter(vec!["choose", "--preview", "1"]);
+        let mut out = Vec::new();
+        print_inline(&config, &lines(&[""]), &mut out).unwrap();
+        assert_eq!("\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn table_aligns_columns() {
+        let config = Config::from_iter(vec!["choose", "--preview", "2", "--table"]);
+        let mut out = Vec::new();
+        print_table(&config, &lines(&["rust is cool", "a b"]), &mut out).unwrap();
+        assert_eq!(
+            "0     1   2\nrust  is  cool\na     b\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn table_pads_wide_characters_by_char() {
+        let config = Config::from_iter(vec!["choose", "--preview", "2", "--table"]);
+        let mut out = Vec::new();
+        print_table(&config, &lines(&["é x", "abc y"]), &mut out).unwrap();
+        assert_eq!("0    1\né    x\nabc  y\n", String::from_utf8(out).unwrap());
+    }
+}
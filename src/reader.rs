@@ -1,25 +1,296 @@
+use regex::bytes::Regex;
 use std::io::{self, prelude::*};
 
+/// How far back from the end of the buffered input a record separator that has not matched yet
+/// is looked for again once more input arrives. Separators that only match once they are longer
+/// than this may be missed where they span two reads.
+const MATCH_OVERLAP: usize = 4096;
+
+lazy_static! {
+    static ref PARAGRAPH_RE: Regex = Regex::new(r"\n(?:[ \t]*\n)+").unwrap();
+}
+
+/// What ends a record on input
+#[derive(Debug, Clone)]
+pub enum Terminator {
+    /// A single byte, usually a newline
+    Byte(u8),
+    /// Any match of a regular expression
+    Regex(Regex),
+    /// One or more blank lines; empty records are skipped
+    Paragraph,
+}
+
 pub struct BufReader<R> {
+    input: Input<R>,
+    terminator: Terminator,
+}
+
+struct Input<R> {
     reader: io::BufReader<R>,
+    pending: Vec<u8>,
+    eof: bool,
 }
 
 impl<R: Read> BufReader<R> {
-    pub fn new(f: R) -> Self {
+    /// Create a reader whose records end with `terminator`
+    pub fn new(f: R, terminator: Terminator) -> Self {
         Self {
-            reader: io::BufReader::new(f),
+            input: Input {
+                reader: io::BufReader::new(f),
+                pending: Vec::new(),
+                eof: false,
+            },
+            terminator,
         }
     }
 
+    /// Read the next record into `buffer`, without its terminator
     pub fn read_line<'buf>(
         &mut self,
         buffer: &'buf mut String,
     ) -> Option<io::Result<&'buf mut String>> {
-        buffer.clear();
+        // reuse the allocation of `buffer` for the raw bytes of the record
+        let mut bytes = std::mem::take(buffer).into_bytes();
+        bytes.clear();
+
+        let found = match &self.terminator {
+            Terminator::Byte(b) => self.input.read_until_byte(*b, &mut bytes),
+            Terminator::Regex(re) => self.input.read_until_match(re, &mut bytes),
+            Terminator::Paragraph => loop {
+                match self.input.read_until_match(&PARAGRAPH_RE, &mut bytes) {
+                    Ok(true) if bytes.iter().all(u8::is_ascii_whitespace) => bytes.clear(),
+                    r => break r,
+                }
+            },
+        };
+
+        match found {
+            Ok(false) => None,
+            Ok(true) => match String::from_utf8(bytes) {
+                Ok(s) => {
+                    *buffer = s;
+                    Some(Ok(buffer))
+                }
+                Err(e) => Some(Err(io::Error::new(io::ErrorKind::InvalidData, e))),
+            },
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl<R: Read> Input<R> {
+    fn read_until_byte(&mut self, terminator: u8, bytes: &mut Vec<u8>) -> io::Result<bool> {
+        if self.reader.read_until(terminator, bytes)? == 0 {
+            return Ok(false);
+        }
+
+        if bytes.last() == Some(&terminator) {
+            bytes.pop();
+        }
+
+        Ok(true)
+    }
+
+    fn read_until_match(&mut self, re: &Regex, bytes: &mut Vec<u8>) -> io::Result<bool> {
+        // where the earliest match not yet ruled out could start
+        let mut scanned = 0;
+        loop {
+            // a match touching the end of the buffered input might continue into the next
+            // chunk, so only accept it once more input has arrived or there is none left
+            let found = find_nonempty(re, &self.pending, scanned);
+            match found {
+                Some((start, end)) if self.eof || end < self.pending.len() => {
+                    bytes.extend_from_slice(&self.pending[..start]);
+                    self.pending.drain(..end);
+                    return Ok(true);
+                }
+                Some((start, _)) => scanned = start,
+                None => scanned = self.pending.len().saturating_sub(MATCH_OVERLAP),
+            }
+
+            if self.eof {
+                if self.pending.is_empty() {
+                    return Ok(false);
+                }
+                bytes.append(&mut self.pending);
+                return Ok(true);
+            }
+
+            let chunk = self.reader.fill_buf()?;
+            if chunk.is_empty() {
+                self.eof = true;
+            }
+            self.pending.extend_from_slice(chunk);
+            let len = chunk.len();
+            self.reader.consume(len);
+        }
+    }
+}
+
+/// Iterator over the records of an in-memory buffer, such as a memory-mapped file
+pub struct Records<'a> {
+    bytes: &'a [u8],
+    terminator: &'a Terminator,
+}
+
+impl<'a> Records<'a> {
+    pub fn new(bytes: &'a [u8], terminator: &'a Terminator) -> Self {
+        Records { bytes, terminator }
+    }
+
+    fn next_record(&mut self) -> Option<&'a [u8]> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+
+        let found = match self.terminator {
+            Terminator::Byte(b) => self.bytes.iter().position(|c| c == b).map(|i| (i, i + 1)),
+            Terminator::Regex(re) => find_nonempty(re, self.bytes, 0),
+            Terminator::Paragraph => find_nonempty(&PARAGRAPH_RE, self.bytes, 0),
+        };
+
+        let (record, rest) = match found {
+            Some((start, end)) => (&self.bytes[..start], &self.bytes[end..]),
+            None => (self.bytes, &self.bytes[self.bytes.len()..]),
+        };
+        self.bytes = rest;
+        Some(record)
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = self.next_record()?;
+            match self.terminator {
+                Terminator::Paragraph if record.iter().all(u8::is_ascii_whitespace) => continue,
+                _ => return Some(record),
+            }
+        }
+    }
+}
+
+/// Leftmost non-empty match of `re` in `bytes` starting at or after `from`
+fn find_nonempty(re: &Regex, bytes: &[u8], from: usize) -> Option<(usize, usize)> {
+    let mut at = from;
+    while at <= bytes.len() {
+        let m = re.find_at(bytes, at)?;
+        if m.end() > m.start() {
+            return Some((m.start(), m.end()));
+        }
+        at = m.end() + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out its input one byte per read, so every match crosses a chunk boundary
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((b, rest)) if !buf.is_empty() => {
+                    buf[0] = *b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    /// Read all records both streaming and from memory, checking that the two agree
+    fn records(input: &[u8], terminator: Terminator) -> Vec<String> {
+        let mut reader = BufReader::new(Trickle(input), terminator.clone());
+        let mut buffer = String::new();
+        let mut records = Vec::new();
+        while let Some(record) = reader.read_line(&mut buffer) {
+            records.push(record.unwrap().clone());
+        }
+
+        let in_memory: Vec<String> = Records::new(input, &terminator)
+            .map(|r| String::from_utf8(r.to_vec()).unwrap())
+            .collect();
+        assert_eq!(records, in_memory);
+
+        records
+    }
+
+    #[test]
+    fn read_newline_terminated() {
+        assert_eq!(
+            vec!["a b", "c d"],
+            records(b"a b\nc d\n", Terminator::Byte(b'\n'))
+        );
+    }
+
+    #[test]
+    fn read_nul_terminated_without_final_terminator() {
+        assert_eq!(
+            vec!["a\nb", "c"],
+            records(b"a\nb\0c", Terminator::Byte(b'\0'))
+        );
+    }
+
+    #[test]
+    fn read_regex_terminated_across_chunks() {
+        let re = Regex::new(";+").unwrap();
+        assert_eq!(
+            vec!["a", "b", "c"],
+            records(b"a;;;b;c", Terminator::Regex(re))
+        );
+    }
+
+    #[test]
+    fn read_regex_terminated_long_records() {
+        let re = Regex::new(r"\bEND\b").unwrap();
+        let long = "x".repeat(MATCH_OVERLAP + 1);
+        let input = format!("{} END {}ENDING END b", long, long);
+        assert_eq!(
+            vec![
+                format!("{} ", long),
+                format!(" {}ENDING ", long),
+                String::from(" b")
+            ],
+            records(input.as_bytes(), Terminator::Regex(re))
+        );
+    }
+
+    #[test]
+    fn read_regex_terminated_by_long_match() {
+        let re = Regex::new(";+").unwrap();
+        let input = format!("a{}b", ";".repeat(MATCH_OVERLAP + 1));
+        assert_eq!(
+            vec!["a", "b"],
+            records(input.as_bytes(), Terminator::Regex(re))
+        );
+    }
+
+    #[test]
+    fn read_regex_terminated_keeps_empty_records() {
+        let re = Regex::new(";").unwrap();
+        assert_eq!(vec!["a", "", "b"], records(b"a;;b;", Terminator::Regex(re)));
+    }
+
+    #[test]
+    fn read_paragraphs() {
+        assert_eq!(
+            vec!["a\nb", "c\nd\n"],
+            records(b"\n\na\nb\n\n \n\nc\nd\n", Terminator::Paragraph)
+        );
+    }
 
-        self.reader
-            .read_line(buffer)
-            .map(|u| if u == 0 { None } else { Some(buffer) })
-            .transpose()
+    #[test]
+    fn read_invalid_utf8() {
+        let mut reader = BufReader::new(&b"\xff\n"[..], Terminator::Byte(b'\n'));
+        let mut buffer = String::new();
+        assert!(reader.read_line(&mut buffer).unwrap().is_err());
     }
 }
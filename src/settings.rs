@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use structopt::clap::ErrorKind;
+use structopt::StructOpt;
+
+use crate::error::ChooseError;
+use crate::man;
+use crate::opt::Opt;
+
+/// Environment variables that supply defaults, the long flag each one sets and whether that flag
+/// is a switch taking no value
+pub const ENV_VARS: &[(&str, &str, bool)] = &[
+    ("CHOOSE_SEPARATOR", "field-separator", false),
+    ("CHOOSE_OUTPUT_SEPARATOR", "output-field-separator", false),
+    ("CHOOSE_PRESET", "preset", false),
+    ("CHOOSE_MAX_OPEN_FILES", "max-open-files", false),
+    ("CHOOSE_EXCLUSIVE", "exclusive", true),
+    ("CHOOSE_LINE_BUFFERED", "line-buffered", true),
+];
+
+/// A value in the config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+}
+
+/// Default flags and named aliases read from the user's config file, for example
+///
+/// ```toml
+/// [defaults]
+/// line-buffered = true
+///
+/// [aliases]
+/// passwd = "-f : 0 6"
+/// ```
+///
+/// and default flags read from the [`ENV_VARS`], which win over the config file.
+#[derive(Debug, Default)]
+pub struct Settings {
+    /// The config file read, if there was one
+    pub path: Option<PathBuf>,
+    pub defaults: Vec<(String, Value)>,
+    pub aliases: HashMap<String, String>,
+    /// Defaults from the environment as the variable, the long flag it sets and its value
+    pub environment: Vec<(&'static str, &'static str, Value)>,
+}
+
+impl Settings {
+    /// Where the config file lives: `$XDG_CONFIG_HOME/choose/config.toml`, falling back to
+    /// `~/.config/choose/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("choose").join("config.toml"))
+    }
+
+    /// Read the config file, if there is one
+    pub fn load() -> Result<Self, ChooseError> {
+        let path = match Settings::path() {
+            Some(path) => path,
+            None => return Ok(Settings::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => match Settings::parse(&text) {
+                Ok(settings) => Ok(Settings {
+                    path: Some(path),
+                    ..settings
+                }),
+                Err(e) => Err(ChooseError::Settings(path, e)),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(e) => Err(ChooseError::Settings(path, e.to_string())),
+        }
+    }
+
+    /// Parse the small subset of TOML the config file needs: `[defaults]` and `[aliases]`
+    /// tables of keys set to strings, integers or booleans, and `#` comments
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut settings = Settings::default();
+        let mut table = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let fail = |message: &str| format!("line {}: {}", number + 1, message);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                match name.trim() {
+                    name @ "defaults" | name @ "aliases" => table = Some(name),
+                    name => return Err(fail(&format!("unknown table [{}]", name))),
+                }
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => return Err(fail("expected `key = value`")),
+            };
+            let key = unquote(key).unwrap_or_else(|| key.to_string());
+            if key.is_empty() {
+                return Err(fail("missing key"));
+            }
+            let value =
+                parse_value(value).ok_or_else(|| fail("expected a string, integer or boolean"))?;
+
+            match (table, value) {
+                (Some("defaults"), value) => settings.defaults.push((key, value)),
+                (Some("aliases"), Value::String(s)) => {
+                    settings.aliases.insert(key, s);
+                }
+                (Some("aliases"), _) => return Err(fail("aliases must be strings")),
+                _ => return Err(fail("keys must be inside [defaults] or [aliases]")),
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Read the defaults set by the [`ENV_VARS`] among `vars`. Empty variables are ignored, and
+    /// switches are set with `1`, `true` or `yes` and cleared with `0`, `false` or `no`.
+    pub fn read_env<I: IntoIterator<Item = (OsString, OsString)>>(&mut self, vars: I) {
+        let vars: HashMap<OsString, OsString> = vars.into_iter().collect();
+        for &(var, key, switch) in ENV_VARS {
+            let value = match vars.get(OsStr::new(var)).and_then(|v| v.to_str()) {
+                Some("") | None => continue,
+                Some(value) => value,
+            };
+            let value = match value {
+                _ if !switch => Value::String(value.to_string()),
+                "1" | "true" | "yes" => Value::Bool(true),
+                "0" | "false" | "no" => Value::Bool(false),
+                _ => {
+                    eprintln!("choose: ignoring {}: expected true or false", var);
+                    continue;
+                }
+            };
+            self.environment.push((var, key, value));
+        }
+    }
+
+    /// Replace each `@name` choice with the arguments of the alias it names. Option values and
+    /// `@` arguments that don't name an alias are left as they are.
+    pub fn expand_aliases(&self, args: Vec<OsString>) -> Vec<OsString> {
+        let choices: Vec<usize> = match Opt::clap().get_matches_from_safe(&args) {
+            Ok(matches) => matches.indices_of("choice").into_iter().flatten().collect(),
+            // leave reporting the error to the real parse
+            Err(_) => return args,
+        };
+
+        let mut expanded = Vec::with_capacity(args.len());
+        for (i, arg) in args.into_iter().enumerate() {
+            let alias = arg
+                .to_str()
+                .and_then(|a| a.strip_prefix('@'))
+                .filter(|_| choices.contains(&i))
+                .and_then(|name| self.aliases.get(name));
+            match alias {
+                Some(alias) => expanded.extend(split_words(alias).into_iter().map(OsString::from)),
+                None => expanded.push(arg),
+            }
+        }
+        expanded
+    }
+
+    /// Add the default flags the command line doesn't already give. A default that would make
+    /// the command line invalid, for example by conflicting with a flag given, is left out.
+    pub fn apply_defaults(&self, args: Vec<OsString>) -> Vec<OsString> {
+        let matches = match Opt::clap().get_matches_from_safe(&args) {
+            Ok(matches) => matches,
+            // leave reporting the error to the real parse
+            Err(_) => return args,
+        };
+
+        // the environment overrides the config file
+        let file_defaults = self
+            .defaults
+            .iter()
+            .filter(|(key, _)| self.env_var(key).is_none())
+            .map(|(key, value)| (key.as_str(), value));
+        let env_defaults = self.environment.iter().map(|(_, key, value)| (*key, value));
+
+        let mut args = args;
+        for (key, value) in env_defaults.chain(file_defaults) {
+            if matches.occurrences_of(key) > 0 {
+                continue;
+            }
+            let mut flag = vec![OsString::from(format!("--{}", key))];
+            match value {
+                Value::Bool(false) => continue,
+                Value::Bool(true) => (),
+                Value::Integer(i) => flag.push(OsString::from(i.to_string())),
+                Value::String(s) => flag.push(OsString::from(s)),
+            }
+
+            // defaults go before the given arguments, so that trailing choices stay last
+            let mut with_default = args[..1].to_vec();
+            with_default.extend(flag);
+            with_default.extend_from_slice(&args[1..]);
+            match Opt::clap().get_matches_from_safe(&with_default) {
+                Ok(_) => args = with_default,
+                // a default that conflicts with a flag given is expected and left out quietly
+                Err(e) if e.kind == ErrorKind::ArgumentConflict => (),
+                Err(e) => eprintln!(
+                    "choose: ignoring default {}: {}",
+                    key,
+                    e.message.lines().next().unwrap_or_default()
+                ),
+            }
+        }
+        args
+    }
+
+    /// The environment variable setting the default for the long flag `key`, if one does
+    fn env_var(&self, key: &str) -> Option<&'static str> {
+        self.environment
+            .iter()
+            .find(|(_, k, _)| *k == key)
+            .map(|(var, _, _)| *var)
+    }
+
+    /// Print the effective value of every flag and option, as config file lines commented with
+    /// where each came from. `given` are the arguments from the command line and `args` the
+    /// ones parsed, after [`apply_defaults`](Settings::apply_defaults).
+    pub fn print_config<W: Write>(
+        &self,
+        given: &[OsString],
+        args: &[OsString],
+        handle: &mut W,
+    ) -> io::Result<()> {
+        let given = Opt::clap().get_matches_from_safe(given).ok();
+        let matches = Opt::clap()
+            .get_matches_from_safe(args)
+            .map_err(|e| io::Error::other(e.message))?;
+
+        let help = man::read_help(Opt::clap())?;
+        let entries = help
+            .sections
+            .iter()
+            .filter(|s| s.title == "FLAGS" || s.title == "OPTIONS")
+            .flat_map(|s| &s.entries);
+        for entry in entries {
+            let key = match entry.names.iter().find_map(|n| n.strip_prefix("--")) {
+                Some("help") | Some("version") | Some("print-config") | None => continue,
+                Some(key) => key,
+            };
+            let value = match (&entry.value, matches.value_of(key)) {
+                (None, _) => matches.is_present(key).to_string(),
+                (Some(_), Some(value)) if value.parse::<i64>().is_ok() => value.to_string(),
+                (Some(_), Some(value)) => quote(value),
+                (Some(_), None) => continue,
+            };
+
+            let source = if given.as_ref().is_some_and(|g| g.occurrences_of(key) > 0) {
+                String::from("command line")
+            } else if matches.occurrences_of(key) == 0 {
+                String::from("default")
+            } else if let Some(var) = self.env_var(key) {
+                format!("environment {}", var)
+            } else {
+                let path = self.path.as_ref().map(|p| p.display().to_string());
+                format!("config file {}", path.unwrap_or_default())
+            };
+            writeln!(handle, "{} = {}  # {}", key, value, source)?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"') | (None, '\'') => quote = Some(c),
+            // only basic strings have escapes
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if q == c && !escaped => quote = None,
+            _ => (),
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    match value {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => unquote(value)
+            .map(Value::String)
+            .or_else(|| value.replace('_', "").parse().ok().map(Value::Integer)),
+    }
+}
+
+/// The contents of a basic `"..."` string with its escapes resolved, or of a literal `'...'`
+/// string
+fn unquote(s: &str) -> Option<String> {
+    if s.len() >= 2 && s.starts_with('\'') && s.ends_with('\'') {
+        return Some(s[1..s.len() - 1].to_string());
+    }
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unquoted.push(c);
+            continue;
+        }
+        unquoted.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            c @ '"' | c @ '\\' => c,
+            _ => return None,
+        });
+    }
+    Some(unquoted)
+}
+
+/// `s` as a basic string, the reverse of [`unquote`]
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split an alias into arguments at whitespace, keeping whitespace inside single or double
+/// quotes. Unlike [`ShellWords`](crate::shell::ShellWords) there are no backslash escapes or `#`
+/// comments, so an alias like `-f \t 1` reaches the regex with its backslash.
+pub fn split_words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None;
+    let mut quote = None;
+
+    for c in s.chars() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some(q), c) if q == c => quote = None,
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    mod parse_tests {
+        use super::*;
+
+        #[test]
+        fn parse_tables() {
+            let settings = Settings::parse(
+                r#"
+                # team presets
+                [defaults]
+                field-separator = ":"  # passwd style
+                line-buffered = true
+                max-open-files = 4
+
+                [aliases]
+                passwd = "-f : 0 6"
+                tabs = '-f \t 1'
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                vec![
+                    (
+                        String::from("field-separator"),
+                        Value::String(String::from(":"))
+                    ),
+                    (String::from("line-buffered"), Value::Bool(true)),
+                    (String::from("max-open-files"), Value::Integer(4)),
+                ],
+                settings.defaults
+            );
+            assert_eq!("-f : 0 6", settings.aliases["passwd"]);
+            assert_eq!(r"-f \t 1", settings.aliases["tabs"]);
+        }
+
+        #[test]
+        fn parse_escapes_and_hashes_in_strings() {
+            let settings =
+                Settings::parse("[aliases]\nhash = \"-f \\\"#\\\" 0\" # comment").unwrap();
+            assert_eq!("-f \"#\" 0", settings.aliases["hash"]);
+        }
+
+        #[test]
+        fn parse_key_outside_table() {
+            assert_eq!(
+                Err(String::from(
+                    "line 1: keys must be inside [defaults] or [aliases]"
+                )),
+                Settings::parse("debug = true").map(|_| ())
+            );
+        }
+
+        #[test]
+        fn parse_unknown_table() {
+            assert_eq!(
+                Err(String::from("line 2: unknown table [colors]")),
+                Settings::parse("\n[colors]").map(|_| ())
+            );
+        }
+
+        #[test]
+        fn parse_bad_value() {
+            assert_eq!(
+                Err(String::from(
+                    "line 2: expected a string, integer or boolean"
+                )),
+                Settings::parse("[defaults]\nmax-open-files = four").map(|_| ())
+            );
+        }
+
+        #[test]
+        fn parse_alias_not_string() {
+            assert!(Settings::parse("[aliases]\nx = 1").is_err());
+        }
+    }
+
+    mod alias_tests {
+        use super::*;
+
+        fn settings() -> Settings {
+            Settings::parse("[aliases]\npasswd = \"-f : 0 6\"\nspace = \"-f ' ' 1\"").unwrap()
+        }
+
+        #[test]
+        fn expand_alias() {
+            assert_eq!(
+                args(&["choose", "-f", ":", "0", "6", "-i", "/etc/passwd"]),
+                settings().expand_aliases(args(&["choose", "@passwd", "-i", "/etc/passwd"]))
+            );
+        }
+
+        #[test]
+        fn expand_quoted_alias() {
+            assert_eq!(
+                args(&["choose", "-f", " ", "1"]),
+                settings().expand_aliases(args(&["choose", "@space"]))
+            );
+        }
+
+        #[test]
+        fn expand_unknown_alias() {
+            assert_eq!(
+                args(&["choose", "@nope"]),
+                settings().expand_aliases(args(&["choose", "@nope"]))
+            );
+        }
+
+        #[test]
+        fn expand_leaves_option_values() {
+            for given in &[
+                &["choose", "-f", "@passwd", "1"][..],
+                &["choose", "-o", "@space", "0", "1"],
+                &["choose", "0", "-i", "@passwd"],
+                &["choose", "--field-separator=@passwd", "0"],
+            ] {
+                assert_eq!(args(given), settings().expand_aliases(args(given)));
+            }
+        }
+
+        #[test]
+        fn split_words_quotes() {
+            assert_eq!(
+                vec!["-f", "a b", "", "0"],
+                split_words(r#" -f "a b" '' 0 "#)
+            );
+        }
+    }
+
+    mod defaults_tests {
+        use super::*;
+
+        fn opt(defaults: &str, given: &[&str]) -> Opt {
+            let settings = Settings::parse(&format!("[defaults]\n{}", defaults)).unwrap();
+            Opt::from_iter(settings.apply_defaults(args(given)))
+        }
+
+        #[test]
+        fn defaults_apply() {
+            let opt = opt(
+                "field-separator = \":\"\nline-buffered = true",
+                &["choose", "0"],
+            );
+            assert_eq!(Some(String::from(":")), opt.field_separator);
+            assert!(opt.line_buffered);
+            assert_eq!(1, opt.choice.len());
+        }
+
+        #[test]
+        fn given_flags_win() {
+            let opt = opt("field-separator = \":\"", &["choose", "-f", ",", "0"]);
+            assert_eq!(Some(String::from(",")), opt.field_separator);
+        }
+
+        #[test]
+        fn false_flags_are_left_out() {
+            assert!(!opt("line-buffered = false", &["choose", "0"]).line_buffered);
+        }
+
+        #[test]
+        fn conflicting_defaults_are_left_out() {
+            let opt = opt("zero-terminated = true", &["choose", "-p", "0"]);
+            assert!(!opt.zero_terminated);
+            assert!(opt.paragraph);
+        }
+
+        #[test]
+        fn unknown_defaults_are_left_out() {
+            let opt = opt("colour = true\nmax-open-files = 8", &["choose", "0"]);
+            assert_eq!(8, opt.max_open_files);
+        }
+    }
+
+    mod env_tests {
+        use super::*;
+
+        fn settings(file: &str, vars: &[(&str, &str)]) -> Settings {
+            let mut settings = Settings::parse(&format!("[defaults]\n{}", file)).unwrap();
+            settings.read_env(
+                vars.iter()
+                    .map(|(var, value)| (OsString::from(var), OsString::from(value))),
+            );
+            settings
+        }
+
+        fn print_config(settings: &Settings, given: &[&str]) -> String {
+            let given = args(given);
+            let args = settings.apply_defaults(given.clone());
+            let mut output = Vec::new();
+            settings.print_config(&given, &args, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        }
+
+        #[test]
+        fn read_env_vars() {
+            let settings = settings(
+                "",
+                &[
+                    ("CHOOSE_SEPARATOR", ":"),
+                    ("CHOOSE_LINE_BUFFERED", "yes"),
+                    ("CHOOSE_JOBS", ""),
+                    ("HOME", "/root"),
+                ],
+            );
+            assert_eq!(
+                vec![
+                    (
+                        "CHOOSE_SEPARATOR",
+                        "field-separator",
+                        Value::String(String::from(":"))
+                    ),
+                    ("CHOOSE_LINE_BUFFERED", "line-buffered", Value::Bool(true)),
+                ],
+                settings.environment
+            );
+        }
+
+        #[test]
+        fn env_overrides_config_file() {
+            let settings = settings(
+                "field-separator = \",\"\nline-buffered = true",
+                &[("CHOOSE_SEPARATOR", ":"), ("CHOOSE_LINE_BUFFERED", "0")],
+            );
+            let opt = Opt::from_iter(settings.apply_defaults(args(&["choose", "0"])));
+            assert_eq!(Some(String::from(":")), opt.field_separator);
+            assert!(!opt.line_buffered);
+        }
+
+        #[test]
+        fn given_flags_override_env() {
+            let settings = settings("", &[("CHOOSE_OUTPUT_SEPARATOR", ",")]);
+            let opt = Opt::from_iter(settings.apply_defaults(args(&["choose", "-o", ";", "0"])));
+            assert_eq!(Some(String::from(";")), opt.output_field_separator);
+        }
+
+        #[test]
+        fn print_config_sources() {
+            let mut settings = settings(
+                "max-open-files = 2\nline-buffered = true",
+                &[("CHOOSE_OUTPUT_SEPARATOR", "\t")],
+            );
+            settings.path = Some(PathBuf::from("/etc/choose.toml"));
+            let config = print_config(&settings, &["choose", "--print-config", "-f", ":"]);
+
+            for line in &[
+                "field-separator = \":\"  # command line",
+                "output-field-separator = \"\\t\"  # environment CHOOSE_OUTPUT_SEPARATOR",
+                "max-open-files = 2  # config file /etc/choose.toml",
+                "line-buffered = true  # config file /etc/choose.toml",
+                "debug = false  # default",
+            ] {
+                assert!(config.lines().any(|l| l == *line), "{} missing", line);
+            }
+            assert!(!config.contains("print-config"));
+            assert!(!config.contains("preset"));
+        }
+
+        #[test]
+        fn quote_round_trips() {
+            let s = "a\t\"b\"\\";
+            assert_eq!(Some(String::from(s)), unquote(&quote(s)));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// How printed fields are quoted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Quote fields for a POSIX shell, so they can be read back by `eval` or `xargs`
+    Shell,
+}
+
+impl FromStr for QuoteStyle {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "shell" => Ok(QuoteStyle::Shell),
+            _ => Err(format!("unknown quoting {}, expected shell", src)),
+        }
+    }
+}
+
+/// Iterator over the words of a line split like a POSIX shell splits a command: at unquoted
+/// blanks, with single and double quotes and backslash escapes removed, and stopping at a `#`
+/// starting a word. An unterminated quote runs to the end of the line.
+#[derive(Debug)]
+pub struct ShellWords<'a> {
+    line: &'a str,
+    position: usize,
+}
+
+impl<'a> ShellWords<'a> {
+    pub fn new(line: &'a str) -> Self {
+        ShellWords { line, position: 0 }
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n'
+}
+
+impl<'a> Iterator for ShellWords<'a> {
+    /// A word without quotes or escapes is borrowed from the line
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        let line = self.line;
+        let rest = &line[self.position..];
+        let start = line.len() - rest.trim_start_matches(is_blank).len();
+        if start == line.len() || line[start..].starts_with('#') {
+            self.position = line.len();
+            return None;
+        }
+
+        // the word is only copied once removing quotes or escapes makes it differ from the line
+        let mut word: Option<String> = None;
+        let unquote = |word: &mut Option<String>, i: usize| {
+            word.get_or_insert_with(|| line[start..i].to_string());
+        };
+        let mut quote = None;
+        let mut end = line.len();
+        let mut chars = line[start..].char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let i = start + i;
+            match (quote, c) {
+                (None, c) if is_blank(c) => {
+                    end = i;
+                    break;
+                }
+                (None, '\'') | (None, '"') => {
+                    unquote(&mut word, i);
+                    quote = Some(c);
+                }
+                (Some(q), c) if q == c => {
+                    unquote(&mut word, i);
+                    quote = None;
+                }
+                (None, '\\') | (Some('"'), '\\') => match chars.peek() {
+                    // inside double quotes only these characters can be escaped
+                    Some(&(_, next)) if quote.is_none() || "$`\"\\\n".contains(next) => {
+                        unquote(&mut word, i);
+                        chars.next();
+                        if next != '\n' {
+                            word.as_mut().unwrap().push(next);
+                        }
+                    }
+                    _ => word.iter_mut().for_each(|w| w.push(c)),
+                },
+                (_, c) => word.iter_mut().for_each(|w| w.push(c)),
+            }
+        }
+
+        self.position = end;
+        Some(word.map_or(Cow::Borrowed(&line[start..end]), Cow::Owned))
+    }
+}
+
+/// `field` quoted for a POSIX shell, left as it is when it has no characters the shell treats
+/// specially
+pub fn quote(field: &str) -> Cow<'_, str> {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !field.is_empty() && field.chars().all(plain) {
+        return Cow::Borrowed(field);
+    }
+    Cow::Owned(format!("'{}'", field.replace('\'', r"'\''")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        ShellWords::new(line).map(String::from).collect()
+    }
+
+    mod shell_words_tests {
+        use super::*;
+
+        #[test]
+        fn split_at_blanks() {
+            assert_eq!(vec!["ls", "-l", "/tmp"], words("  ls \t-l   /tmp  "));
+        }
+
+        #[test]
+        fn single_quotes() {
+            assert_eq!(
+                vec!["echo", "it's \"here\" \\n"],
+                words(r#"echo 'it'\''s "here" \n'"#)
+            );
+        }
+
+        #[test]
+        fn double_quotes() {
+            assert_eq!(
+                vec!["git", "commit", "-m", "say \"hi\" \\o/ $x"],
+                words(r#"git commit -m "say \"hi\" \o/ \$x""#)
+            );
+        }
+
+        #[test]
+        fn backslash_escapes() {
+            assert_eq!(vec!["a b", "c"], words(r"a\ b c"));
+            assert_eq!(vec!["ab"], words("a\\\nb"));
+        }
+
+        #[test]
+        fn quotes_join_a_word() {
+            assert_eq!(vec!["--name=a b"], words(r#"--name="a b""#));
+        }
+
+        #[test]
+        fn empty_quotes_are_a_word() {
+            assert_eq!(vec!["x", "", "y"], words("x '' y"));
+        }
+
+        #[test]
+        fn comments_end_the_line() {
+            assert_eq!(vec!["make", "a#b"], words("make a#b # build it"));
+            assert!(words("# just a comment").is_empty());
+        }
+
+        #[test]
+        fn unterminated_quote_runs_to_the_end() {
+            assert_eq!(vec!["echo", "a b"], words("echo 'a b"));
+        }
+
+        #[test]
+        fn plain_words_are_borrowed() {
+            let mut words = ShellWords::new("plain 'quoted'");
+            assert!(matches!(words.next(), Some(Cow::Borrowed("plain"))));
+            assert!(matches!(words.next(), Some(Cow::Owned(_))));
+        }
+    }
+
+    mod quote_tests {
+        use super::*;
+
+        #[test]
+        fn quote_plain() {
+            assert_eq!("/usr/bin/vim", quote("/usr/bin/vim"));
+            assert_eq!("--name=x", quote("--name=x"));
+        }
+
+        #[test]
+        fn quote_special() {
+            assert_eq!("'a b'", quote("a b"));
+            assert_eq!("'$HOME'", quote("$HOME"));
+            assert_eq!("''", quote(""));
+            assert_eq!(r"'it'\''s'", quote("it's"));
+        }
+
+        #[test]
+        fn quote_round_trips() {
+            for field in &["a b", "it's", "say \"hi\"", "", "tab\there", "back\\slash"] {
+                assert_eq!(vec![field.to_string()], words(&quote(field)));
+            }
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use regex::{CaptureMatches, Captures, Regex, Split};
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use std::slice;
+use std::str::FromStr;
+
+use crate::shell::ShellWords;
+
+/// How a line is cut into fields
+#[derive(Debug, Clone)]
+pub enum Tokenizer {
+    /// Fields are the non-empty text between matches of a separator
+    Separator(Regex),
+    /// Fields are all the text between matches of a delimiter, so empty fields keep their place
+    /// as they do in files like /etc/passwd
+    Delimiter(Regex),
+    /// Fields are the matches of a pattern, or of its first capture group that took part in the
+    /// match, so a field can itself contain what would otherwise separate fields
+    Pattern(Regex),
+    /// Fields are columns of fixed widths
+    Widths(Widths),
+    /// Fields are the words of a shell command, without the quotes and escapes in them
+    ShellWords,
+}
+
+impl Tokenizer {
+    /// The non-empty fields of `line` in order
+    pub fn fields<'a>(&'a self, line: &'a str) -> Tokens<'a> {
+        match self {
+            Tokenizer::Separator(separator) => Tokens::Split(separator.split(line)),
+            Tokenizer::Delimiter(delimiter) => Tokens::Delimited(delimiter.split(line)),
+            Tokenizer::Pattern(pattern) => Tokens::Pattern(line, pattern.captures_iter(line)),
+            Tokenizer::Widths(widths) => Tokens::Widths(line, widths.ranges(line)),
+            Tokenizer::ShellWords => Tokens::ShellWords(ShellWords::new(line)),
+        }
+    }
+}
+
+impl fmt::Display for Tokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tokenizer::Separator(separator) => write!(f, "separator: {}", separator.as_str()),
+            Tokenizer::Delimiter(delimiter) => write!(f, "delimiter: {}", delimiter.as_str()),
+            Tokenizer::Pattern(pattern) => write!(f, "field pattern: {}", pattern.as_str()),
+            Tokenizer::Widths(widths) => write!(f, "widths: {}", widths),
+            Tokenizer::ShellWords => write!(f, "shell words"),
+        }
+    }
+}
+
+/// Iterator returned by [`Tokenizer::fields`]
+pub enum Tokens<'a> {
+    Split(Split<'a, 'a>),
+    Delimited(Split<'a, 'a>),
+    Pattern(&'a str, CaptureMatches<'a, 'a>),
+    Widths(&'a str, WidthRanges<'a>),
+    ShellWords(ShellWords<'a>),
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    /// Fields are borrowed from the line unless they had quotes or escapes removed
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        loop {
+            let field = match self {
+                Tokens::Split(split) => split.next()?,
+                Tokens::Delimited(split) => return split.next().map(Cow::Borrowed),
+                Tokens::Pattern(line, captures) => {
+                    let range = field_range(&captures.next()?);
+                    &line[range]
+                }
+                // columns keep their place even when blank
+                Tokens::Widths(line, ranges) => {
+                    return ranges.next().map(|r| Cow::Borrowed(&line[r]))
+                }
+                // quoted empty words are kept like the shell keeps them
+                Tokens::ShellWords(words) => return words.next(),
+            };
+            if !field.is_empty() {
+                return Some(Cow::Borrowed(field));
+            }
+        }
+    }
+}
+
+/// The range of the field a match of a [`Tokenizer::Pattern`] stands for
+pub fn field_range(captures: &Captures) -> Range<usize> {
+    captures
+        .iter()
+        .skip(1)
+        .flatten()
+        .next()
+        .or_else(|| captures.get(0))
+        .map_or(0..0, |m| m.start()..m.end())
+}
+
+/// Widths in characters of the columns of a fixed-width layout, written like `8,12,5,*`, where a
+/// final `*` is a column taking the rest of the line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Widths {
+    pub widths: Vec<usize>,
+    pub rest: bool,
+}
+
+impl Widths {
+    /// Infer the columns of text aligned with spaces, like the output of `ps aux` or `docker ps`,
+    /// from a sample of `lines` starting with a header. A column starts where a position blank on
+    /// every line is followed by one that isn't, and must hold the start of a word of the header,
+    /// so that spaces inside a column, like in a command line, don't split it.
+    pub fn infer<S: AsRef<str>>(lines: &[S]) -> Widths {
+        let lines: Vec<Vec<char>> = lines.iter().map(|l| l.as_ref().chars().collect()).collect();
+        let len = lines.iter().map(Vec::len).max().unwrap_or(0);
+        let blank = |line: &[char], p: usize| line.get(p).is_none_or(|c| c.is_whitespace());
+        let all_blank = |p: usize| lines.iter().all(|l| blank(l, p));
+        let starts_word =
+            |line: &[char], p: usize| !blank(line, p) && (p == 0 || blank(line, p - 1));
+
+        let candidates: Vec<usize> = (0..len)
+            .filter(|&p| !all_blank(p) && (p == 0 || all_blank(p - 1)))
+            .collect();
+        let header = lines.first().map_or(&[][..], Vec::as_slice);
+        let mut starts = Vec::new();
+        for (i, &start) in candidates.iter().enumerate() {
+            let end = candidates.get(i + 1).copied().unwrap_or(len);
+            if starts.is_empty() || (start..end).any(|p| starts_word(header, p)) {
+                starts.push(start);
+            }
+        }
+
+        // the first column also takes any blanks before it
+        let mut widths = Vec::new();
+        let mut start = 0;
+        for &next in starts.iter().skip(1) {
+            widths.push(next - start);
+            start = next;
+        }
+        Widths { widths, rest: true }
+    }
+
+    /// The byte ranges of the columns of `line`, without the whitespace padding them. A line
+    /// ending partway through the layout has only the columns it reaches.
+    pub fn ranges<'a>(&'a self, line: &'a str) -> WidthRanges<'a> {
+        WidthRanges {
+            line,
+            widths: self.widths.iter(),
+            rest: self.rest,
+            start: 0,
+        }
+    }
+}
+
+impl FromStr for Widths {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut widths = Vec::new();
+        let mut rest = false;
+        for width in src.split(',').map(str::trim) {
+            if rest {
+                return Err(String::from("`*` must be the last width"));
+            }
+            match width.parse() {
+                _ if width == "*" => rest = true,
+                Ok(0) | Err(_) => {
+                    return Err(format!(
+                        "widths are positive numbers of characters or a final `*`, got `{}`",
+                        width
+                    ))
+                }
+                Ok(width) => widths.push(width),
+            }
+        }
+        Ok(Widths { widths, rest })
+    }
+}
+
+impl fmt::Display for Widths {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut widths: Vec<String> = self.widths.iter().map(usize::to_string).collect();
+        if self.rest {
+            widths.push(String::from("*"));
+        }
+        write!(f, "{}", widths.join(","))
+    }
+}
+
+/// Iterator returned by [`Widths::ranges`]
+#[derive(Debug)]
+pub struct WidthRanges<'a> {
+    line: &'a str,
+    widths: slice::Iter<'a, usize>,
+    rest: bool,
+    start: usize,
+}
+
+impl<'a> Iterator for WidthRanges<'a> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        let line = self.line;
+        if self.start >= line.len() {
+            return None;
+        }
+        let end = match self.widths.next() {
+            Some(&width) => line[self.start..]
+                .char_indices()
+                .nth(width)
+                .map_or(line.len(), |(i, _)| self.start + i),
+            None if self.rest => line.len(),
+            None => return None,
+        };
+
+        let column = &line[self.start..end];
+        let start = self.start + (column.len() - column.trim_start().len());
+        let range = start..std::cmp::max(start, end - (column.len() - column.trim_end().len()));
+        self.start = end;
+        Some(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(tokenizer: &Tokenizer, line: &str) -> Vec<String> {
+        tokenizer.fields(line).map(String::from).collect()
+    }
+
+    #[test]
+    fn separator_skips_empty_fields() {
+        let tokenizer = Tokenizer::Separator(Regex::new(":").unwrap());
+        assert_eq!(vec!["a", "b"], fields(&tokenizer, "::a::b:"));
+    }
+
+    #[test]
+    fn pattern_uses_first_group() {
+        let tokenizer = Tokenizer::Pattern(Regex::new(r#""([^"]*)"|(\S+)"#).unwrap());
+        assert_eq!(
+            vec!["GET / HTTP/1.1", "200", "a b"],
+            fields(&tokenizer, r#""GET / HTTP/1.1" 200 "a b""#)
+        );
+    }
+
+    #[test]
+    fn pattern_without_groups_uses_match() {
+        let tokenizer = Tokenizer::Pattern(Regex::new(r"\d+").unwrap());
+        assert_eq!(vec!["12", "3"], fields(&tokenizer, "a12b3"));
+    }
+
+    #[test]
+    fn pattern_skips_empty_fields() {
+        let tokenizer = Tokenizer::Pattern(Regex::new(r#""([^"]*)"|(\S+)"#).unwrap());
+        assert_eq!(vec!["a"], fields(&tokenizer, r#""" a"#));
+    }
+
+    #[test]
+    fn shell_words_keep_empty_words() {
+        assert_eq!(
+            vec!["echo", "", "it's"],
+            fields(&Tokenizer::ShellWords, r#"echo "" it\'s # done"#)
+        );
+    }
+
+    mod widths_tests {
+        use super::*;
+
+        fn widths(src: &str) -> Tokenizer {
+            Tokenizer::Widths(src.parse().unwrap())
+        }
+
+        #[test]
+        fn parse_widths() {
+            assert_eq!(
+                Ok(Widths {
+                    widths: vec![8, 12, 5],
+                    rest: true
+                }),
+                "8, 12,5,*".parse()
+            );
+            assert_eq!(
+                "8,12,5,*",
+                "8, 12,5,*".parse::<Widths>().unwrap().to_string()
+            );
+        }
+
+        #[test]
+        fn parse_bad_widths() {
+            assert!("8,*,5".parse::<Widths>().is_err());
+            assert!("8,0".parse::<Widths>().is_err());
+            assert!("8,x".parse::<Widths>().is_err());
+            assert!("".parse::<Widths>().is_err());
+        }
+
+        #[test]
+        fn widths_trim_padding() {
+            assert_eq!(
+                vec!["JOHN", "SMITH", "42", "rest of  it"],
+                fields(&widths("6,8,4,*"), "JOHN  SMITH     42 rest of  it ")
+            );
+        }
+
+        #[test]
+        fn widths_keep_blank_columns() {
+            assert_eq!(vec!["a", "", "c"], fields(&widths("3,3,3"), "a     c  "));
+        }
+
+        #[test]
+        fn widths_without_rest_drop_the_end() {
+            assert_eq!(vec!["ab", "cd"], fields(&widths("2,2"), "abcdef"));
+        }
+
+        #[test]
+        fn widths_on_short_lines() {
+            assert_eq!(vec!["ab", "c"], fields(&widths("2,2,2,*"), "abc"));
+        }
+
+        #[test]
+        fn widths_count_characters() {
+            assert_eq!(vec!["éé", "ü"], fields(&widths("2,*"), "ééü"));
+        }
+    }
+
+    mod infer_tests {
+        use super::*;
+
+        #[test]
+        fn infer_ps() {
+            let lines = [
+                "USER         PID %CPU COMMAND",
+                "root           1  0.0 /sbin/init splash",
+                "alice       2231  1.3 /usr/bin/firefox --new-window",
+            ];
+            let widths = Widths::infer(&lines);
+            assert_eq!("12,5,5,*", widths.to_string());
+            assert_eq!(
+                vec!["alice", "2231", "1.3", "/usr/bin/firefox --new-window"],
+                fields(&Tokenizer::Widths(widths), lines[2])
+            );
+        }
+
+        #[test]
+        fn infer_spaces_in_header_and_cells() {
+            let lines = [
+                "CONTAINER ID   IMAGE     CREATED       NAMES",
+                "4f1c2d3e5a6b   nginx     2 hours ago   web",
+                "9a8b7c6d5e4f   redis     3 days ago    cache",
+            ];
+            let widths = Widths::infer(&lines);
+            assert_eq!("15,10,14,*", widths.to_string());
+            assert_eq!(
+                vec!["9a8b7c6d5e4f", "redis", "3 days ago", "cache"],
+                fields(&Tokenizer::Widths(widths), lines[2])
+            );
+        }
+
+        #[test]
+        fn infer_blank_cells() {
+            let lines = [
+                "NAME   PORTS   STATUS",
+                "web    80/tcp  up",
+                "cache          up",
+            ];
+            let widths = Widths::infer(&lines);
+            assert_eq!(
+                vec!["cache", "", "up"],
+                fields(&Tokenizer::Widths(widths), lines[2])
+            );
+        }
+
+        #[test]
+        fn infer_nothing() {
+            let empty: [&str; 0] = [];
+            assert_eq!("*", Widths::infer(&empty).to_string());
+            assert_eq!("*", Widths::infer(&["", "  "]).to_string());
+        }
+    }
+}
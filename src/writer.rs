@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::choice::Choice;
+use crate::config::Config;
+use crate::debug;
+use crate::fields::Fields;
+
+/// Prints every choice of a line. With several choices the line is split into fields once and
+/// each choice indexes into them; a single choice streams over the line so it can stop early.
+pub struct LinePrinter {
+    fields: Fields,
+    field_limit: Option<usize>,
+}
+
+impl LinePrinter {
+    pub fn new(config: &Config) -> Self {
+        let field_limit = config
+            .choices
+            .iter()
+            .map(Choice::field_limit)
+            .try_fold(0, |limit, l| l.map(|l| std::cmp::max(limit, l)))
+            // the field holding the rest of the line needs every field after it
+            .filter(|_| config.max_split.is_none());
+
+        LinePrinter {
+            fields: Fields::new(),
+            field_limit,
+        }
+    }
+
+    /// Print the choices of `line` followed by the output terminator
+    pub fn print<T: Write>(
+        &mut self,
+        config: &Config,
+        line: &str,
+        handle: &mut T,
+    ) -> io::Result<()> {
+        match config.choices.as_slice() {
+            [choice] => choice.print_choice(line, config, handle),
+            choices => {
+                self.fields.split(line, &config.tokenizer, self.field_limit);
+                self.fields.limit_splits(config.max_split);
+                for choice in choices {
+                    choice.print_fields(line, &self.fields, config, handle);
+                }
+            }
+        }
+        handle.write_all(&[config.output_terminator])
+    }
+}
+
+/// Writes the chosen fields of each line either to a single handle, usually stdout, or into
+/// per-key files when splitting output with --split-by
+pub struct LineWriter<'a, W: Write> {
+    config: &'a Config,
+    handle: BufWriter<W>,
+    split: Option<(&'a Choice, SplitWriter)>,
+    printer: LinePrinter,
+    line_buffered: bool,
+    record: Vec<u8>,
+    key: String,
+    key_fields: Fields,
+    debug_fields: Fields,
+    line_number: usize,
+}
+
+impl<'a, W: Write> LineWriter<'a, W> {
+    pub fn new(
+        config: &'a Config,
+        handle: W,
+        split_writer: Option<SplitWriter>,
+        line_buffered: bool,
+    ) -> Self {
+        LineWriter {
+            config,
+            handle: BufWriter::new(handle),
+            split: config.split_by.as_ref().zip(split_writer),
+            printer: LinePrinter::new(config),
+            line_buffered,
+            record: Vec::new(),
+            key: String::new(),
+            key_fields: Fields::new(),
+            debug_fields: Fields::new(),
+            line_number: 0,
+        }
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let config = self.config;
+        self.line_number += 1;
+        if config.skips(self.line_number, line) {
+            return Ok(());
+        }
+
+        if config.opt.debug {
+            self.debug_fields.split(line, &config.tokenizer, None);
+            self.debug_fields.limit_splits(config.max_split);
+            debug::print_fields(
+                self.line_number,
+                line,
+                &self.debug_fields,
+                &mut io::stderr().lock(),
+            )?;
+        }
+
+        match &mut self.split {
+            Some((split_by, writer)) => {
+                self.record.clear();
+                self.printer.print(config, line, &mut self.record)?;
+
+                // the key is made of the fields themselves, whatever -o or --quote-output do to
+                // the printed ones
+                let limit = split_by
+                    .field_limit()
+                    .filter(|_| config.max_split.is_none());
+                self.key_fields.split(line, &config.tokenizer, limit);
+                self.key_fields.limit_splits(config.max_split);
+                self.key.clear();
+                for i in split_by.indices(self.key_fields.len()) {
+                    if !self.key.is_empty() {
+                        self.key.push(' ');
+                    }
+                    self.key.push_str(self.key_fields.get(line, i));
+                }
+
+                writer.write_record(&self.key, &self.record)?;
+                if self.line_buffered {
+                    writer.flush()?;
+                }
+            }
+            None => {
+                self.printer.print(config, line, &mut self.handle)?;
+                if self.line_buffered {
+                    self.handle.flush()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some((_, writer)) = &mut self.split {
+            writer.flush()?;
+        }
+        self.handle.flush()
+    }
+}
+
+/// Writes records into one file per key inside a directory, keeping at most `max_open` files
+/// open at a time
+pub struct SplitWriter {
+    dir: PathBuf,
+    max_open: usize,
+    open: HashMap<String, (u64, BufWriter<File>)>,
+    /// Names of the open files by when they were last written to, oldest first
+    last_used: BTreeMap<u64, String>,
+    created: HashSet<String>,
+    clock: u64,
+}
+
+impl SplitWriter {
+    pub fn new(dir: PathBuf, max_open: usize) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(SplitWriter {
+            dir,
+            max_open: std::cmp::max(max_open, 1),
+            open: HashMap::new(),
+            last_used: BTreeMap::new(),
+            created: HashSet::new(),
+            clock: 0,
+        })
+    }
+
+    pub fn write_record(&mut self, key: &str, record: &[u8]) -> io::Result<()> {
+        let name = sanitize(key);
+        self.clock += 1;
+
+        if !self.open.contains_key(&name) {
+            if self.open.len() >= self.max_open {
+                self.evict()?;
+            }
+
+            // files are truncated the first time they are seen in this run and appended to when
+            // reopened after being evicted from the pool
+            let first_open = self.created.insert(name.clone());
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(first_open)
+                .append(!first_open)
+                .open(self.dir.join(&name))?;
+            self.open.insert(name.clone(), (0, BufWriter::new(file)));
+        }
+
+        let (last_used, handle) = self.open.get_mut(&name).unwrap();
+        self.last_used.remove(last_used);
+        *last_used = self.clock;
+        handle.write_all(record)?;
+        self.last_used.insert(self.clock, name);
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for (_, handle) in self.open.values_mut() {
+            handle.flush()?;
+        }
+        Ok(())
+    }
+
+    fn evict(&mut self) -> io::Result<()> {
+        let oldest = self.last_used.keys().next().copied();
+        let name = oldest.and_then(|clock| self.last_used.remove(&clock));
+
+        match name.and_then(|name| self.open.remove(&name)) {
+            Some((_, mut handle)) => handle.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Turn a field value into a file name that stays inside the output directory. Values that had
+/// to be changed get a hash of the original appended, so `a b` and `a/b` go to different files.
+pub fn sanitize(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if name.is_empty() || name.starts_with('.') {
+        name.insert(0, '_');
+    }
+    if name != key {
+        // `+` never survives sanitizing, so these names can't clash with an unchanged value
+        name.push_str(&format!("+{:08x}", fnv1a(key.as_bytes())));
+    }
+    name
+}
+
+/// 32-bit FNV-1a, which unlike `DefaultHasher` gives the same file names in every build
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, b| {
+        (hash ^ u32::from(*b)).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod sanitize_tests {
+        use super::*;
+
+        #[test]
+        fn sanitize_plain() {
+            assert_eq!("root", sanitize("root"));
+        }
+
+        #[test]
+        fn sanitize_path_separators() {
+            assert_eq!("_.._etc_passwd+62dab22f", sanitize("../etc/passwd"));
+        }
+
+        #[test]
+        fn sanitize_spaces() {
+            assert_eq!("a_b+10a3f9f2", sanitize("a b"));
+        }
+
+        #[test]
+        fn sanitize_empty() {
+            assert_eq!("_+811c9dc5", sanitize(""));
+        }
+
+        #[test]
+        fn sanitize_dot() {
+            assert_eq!("_.+2b0c98f1", sanitize("."));
+        }
+
+        #[test]
+        fn sanitize_keeps_changed_values_apart() {
+            let names: HashSet<String> = vec!["a b", "a/b", "a_b", "_", ""]
+                .into_iter()
+                .map(sanitize)
+                .collect();
+            assert_eq!(5, names.len());
+        }
+    }
+
+    mod line_printer_tests {
+        use super::*;
+
+        fn print(args: Vec<&str>, line: &str) -> String {
+            let mut argv = vec!["choose"];
+            argv.extend(args);
+            let config = Config::from_iter(argv);
+            let mut output = Vec::new();
+            LinePrinter::new(&config)
+                .print(&config, line, &mut output)
+                .unwrap();
+            String::from_utf8(output).unwrap()
+        }
+
+        #[test]
+        fn print_with_trailing_separator() {
+            assert_eq!("b c \n", print(vec!["1:2"], "a b c"));
+        }
+
+        #[test]
+        fn print_output_separator() {
+            assert_eq!("a,c,\n", print(vec!["-o", ",", "0", "2"], "a b c"));
+        }
+
+        #[test]
+        fn print_output_separator_single_choice() {
+            assert_eq!("c::b::\n", print(vec!["-o", "::", "2:1"], "a b c"));
+        }
+
+        #[test]
+        fn print_max_split_single_choice() {
+            assert_eq!(
+                "vim -O  a b \n",
+                print(vec!["--max-split", "1", "1"], "  412 vim -O  a b")
+            );
+        }
+
+        #[test]
+        fn print_max_split_choices() {
+            assert_eq!(
+                "vim -O  a b,412,\n",
+                print(
+                    vec!["--max-split", "1", "-o", ",", "-1", "0"],
+                    "  412 vim -O  a b"
+                )
+            );
+        }
+
+        #[test]
+        fn print_rmax_split() {
+            assert_eq!(
+                "4096 a  b \n",
+                print(vec!["--rmax-split", "1", "1", "0"], "a  b 4096")
+            );
+        }
+
+        #[test]
+        fn print_shell_words_quoted() {
+            let line = r#"git commit -m "it's done" --author='A B'"#;
+            assert_eq!(
+                "git 'it'\\''s done' '--author=A B' \n",
+                print(
+                    vec!["--shell-words", "--quote-output", "shell", "0", "3:4"],
+                    line
+                )
+            );
+            assert_eq!(
+                "'it'\\''s done' \n",
+                print(vec!["--shell-words", "--quote-output", "shell", "3"], line)
+            );
+        }
+
+        #[test]
+        fn print_nothing_chosen() {
+            assert_eq!("\n", print(vec!["-o", ",", "5"], "a b c"));
+        }
+    }
+
+    mod split_writer_tests {
+        use super::*;
+
+        fn out_dir(name: &str) -> PathBuf {
+            let dir = std::env::temp_dir().join(format!("choose-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            dir
+        }
+
+        #[test]
+        fn write_reopens_evicted_files_for_append() {
+            let dir = out_dir("evict");
+            let mut writer = SplitWriter::new(dir.clone(), 1).unwrap();
+            writer.write_record("a", b"1\n").unwrap();
+            writer.write_record("b", b"2\n").unwrap();
+            writer.write_record("a", b"3\n").unwrap();
+            writer.flush().unwrap();
+            drop(writer);
+
+            assert_eq!("1\n3\n", fs::read_to_string(dir.join("a")).unwrap());
+            assert_eq!("2\n", fs::read_to_string(dir.join("b")).unwrap());
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn write_evicts_least_recently_used() {
+            let dir = out_dir("lru");
+            let mut writer = SplitWriter::new(dir.clone(), 2).unwrap();
+            for key in &["a", "b", "a", "c"] {
+                writer.write_record(key, b"x\n").unwrap();
+            }
+
+            assert!(writer.open.contains_key("a"));
+            assert!(!writer.open.contains_key("b"));
+            assert_eq!(2, writer.last_used.len());
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn write_line_keys_files_by_field_values() {
+            let dir = out_dir("key");
+            let config = Config::from_iter(vec![
+                "choose",
+                "0",
+                "2",
+                "--split-by",
+                "1",
+                "--out-dir",
+                dir.to_str().unwrap(),
+                "-o",
+                ",",
+                "--quote-output",
+                "shell",
+            ]);
+            let writer = SplitWriter::new(dir.clone(), 4).unwrap();
+            let mut output = LineWriter::new(&config, Vec::new(), Some(writer), false);
+            output.write_line("a root c").unwrap();
+            output.flush().unwrap();
+
+            assert_eq!("a,c,\n", fs::read_to_string(dir.join("root")).unwrap());
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn write_truncates_files_from_previous_runs() {
+            let dir = out_dir("truncate");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("a"), "old\n").unwrap();
+
+            let mut writer = SplitWriter::new(dir.clone(), 4).unwrap();
+            writer.write_record("a", b"new\n").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!("new\n", fs::read_to_string(dir.join("a")).unwrap());
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
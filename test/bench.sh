@@ -7,4 +7,5 @@ for i in {0..4}
 do
   echo ${inputs[$i]}
   bench "target/release/choose 3:5 -i ${inputs[$i]}"  > $output/$1$i.bench
+  bench "target/release/choose 0 1 2 3 4 -i ${inputs[$i]}"  > $output/$1$i-many.bench
 done
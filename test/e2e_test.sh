@@ -14,6 +14,37 @@ diff -w <(cargo run -- 9 -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir
 diff -w <(cargo run -- 12 -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir}/choose_12.txt")
 diff -w <(cargo run -- 4:2 -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir}/choose_4:2.txt")
 diff -w <(cargo run -- -4:-2 -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir}/choose_-4:-2.txt")
+# NUL-terminated records
+diff -w <(tr '\n' '\0' < ${test_dir}/lorem.txt | cargo run -- -z 0:1 2>/dev/null | tr '\0' '\n') <(cat "${test_dir}/choose_0:1.txt")
+diff -w <(tr '\n' '\0' < ${test_dir}/lorem.txt | cargo run -- --null-input 0:1 2>/dev/null) <(cat "${test_dir}/choose_0:1.txt")
+# multi-line records
+diff -w <(cargo run -- -p -f '\s*:\s*' 1 3 5 -i ${test_dir}/cpuinfo.txt 2>/dev/null) <(cat "${test_dir}/choose_-p_1_3_5.txt")
+diff -w <(cargo run -- --record-separator '\n\n' -f '\s*:\s*' 1 3 5 -i ${test_dir}/cpuinfo.txt 2>/dev/null) <(cat "${test_dir}/choose_-p_1_3_5.txt")
+# output split into files by a key field
+out_dir=$(mktemp -d)
+cargo run -- 0 2 --split-by 1 --out-dir ${out_dir} --max-open-files 2 -i ${test_dir}/lorem.txt 2>/dev/null
+diff -w <(cd ${out_dir} && grep -r . | sort) <(cat "${test_dir}/choose_0_2_--split-by_1.txt")
+rm -rf ${out_dir}
+# index-annotated preview
+diff -w <(cargo run -- --preview 3 --table -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir}/choose_--preview_3_--table.txt")
+# output separator
+diff <(cargo run -- -o , 0 3 -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir}/choose_-o_,_0_3.txt")
+diff <(CHOOSE_OUTPUT_SEPARATOR=, cargo run -- 0 3 -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir}/choose_-o_,_0_3.txt")
+# fixed-width columns
+diff <(cargo run -- --widths 8,12,8 -o , 1 2 -i ${test_dir}/report.txt 2>/dev/null) <(cat "${test_dir}/choose_--widths_8,12,8_-o_,_1_2.txt")
+diff <(cargo run -- --auto-columns 4 -o , 6 3 4 -i ${test_dir}/docker.txt 2>/dev/null) <(cat "${test_dir}/choose_--auto-columns_4_-o_,_6_3_4.txt")
+diff <(cat ${test_dir}/docker.txt | cargo run -- --auto-columns 2 -o , 6 3 4 2>/dev/null) <(cat "${test_dir}/choose_--auto-columns_4_-o_,_6_3_4.txt")
+# limited splits
+diff <(cargo run -- --max-split 1 1 -i ${test_dir}/ps-args.txt 2>/dev/null) <(cat "${test_dir}/choose_--max-split_1_1.txt")
+diff <(cargo run -- --rmax-split 2 0 -i ${test_dir}/lorem.txt 2>/dev/null) <(cat "${test_dir}/choose_--rmax-split_2_0.txt")
+# shell words
+diff <(cargo run -- --shell-words --quote-output shell 1: -i ${test_dir}/history.txt 2>/dev/null) <(cat "${test_dir}/choose_--shell-words_--quote-output_shell_1:.txt")
+# presets
+diff -w <(cargo run -- --preset passwd user shell -i ${test_dir}/passwd.txt 2>/dev/null) <(cat "${test_dir}/choose_--preset_passwd_user_shell.txt")
+diff -w <(cargo run -- --preset passwd user home shell -i ${test_dir}/passwd.txt 2>/dev/null) <(cat "${test_dir}/choose_--preset_passwd_user_home_shell.txt")
+diff -w <(cargo run -- --preset ps pid command: -i ${test_dir}/ps.txt 2>/dev/null) <(cat "${test_dir}/choose_--preset_ps_pid_command:.txt")
+diff -w <(cargo run -- --preset ps pid command -i ${test_dir}/ps.txt 2>/dev/null) <(cat "${test_dir}/choose_--preset_ps_pid_command.txt")
+diff -w <(cargo run -- --preset nginx addr status request -i ${test_dir}/access.log 2>/dev/null) <(cat "${test_dir}/choose_--preset_nginx_addr_status_request.txt")
 # add tests for different delimiters
 # add tests using piping
 
@@ -27,6 +58,13 @@ if [ $r -ne 1 ]; then
   exit 1
 fi
 
+cargo run -- --preset passwd login -i ${test_dir}/passwd.txt >&/dev/null
+r=$?
+if [ $r -ne 1 ]; then
+  echo "Failed to return 1 on unknown preset column"
+  exit 1
+fi
+
 cargo run -- 3 -f "[[]" -i ${test_dir}/lorem.txt >&/dev/null
 r=$?
 if [ $r -ne 2 ]; then
@@ -0,0 +1,67 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "choose.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                      \
+    do {                                                                 \
+        if (!(cond)) {                                                   \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,       \
+                    __LINE__, #cond);                                    \
+            failures++;                                                  \
+        }                                                                \
+    } while (0)
+
+static void test_select(void) {
+    const char *line = "rust is pretty cool";
+    ChooseField fields[4];
+    ChooseSelector *selector = choose_selector_new("-1 0:1", NULL);
+    CHECK(selector != NULL);
+
+    ptrdiff_t n = choose_selector_apply(selector, (const uint8_t *)line,
+                                        strlen(line), fields, 4);
+    CHECK(n == 3);
+    CHECK(fields[0].start == 15 && fields[0].end == 19);
+    CHECK(fields[1].start == 0 && fields[1].end == 4);
+    CHECK(fields[2].start == 5 && fields[2].end == 7);
+
+    choose_selector_free(selector);
+}
+
+static void test_small_capacity(void) {
+    const char *line = "a:b:c:d";
+    ChooseField fields[1];
+    ChooseSelector *selector = choose_selector_new("1:3", ":");
+    CHECK(selector != NULL);
+
+    ptrdiff_t n = choose_selector_apply(selector, (const uint8_t *)line,
+                                        strlen(line), fields, 1);
+    CHECK(n == 3);
+    CHECK(fields[0].start == 2 && fields[0].end == 3);
+
+    choose_selector_free(selector);
+}
+
+static void test_errors(void) {
+    const uint8_t bad_utf8[] = {0xff, 0xfe};
+    ChooseField fields[1];
+
+    CHECK(choose_selector_new("nope", NULL) == NULL);
+    CHECK(choose_selector_new("0", "[[") == NULL);
+
+    ChooseSelector *selector = choose_selector_new("0", NULL);
+    CHECK(choose_selector_apply(selector, bad_utf8, sizeof bad_utf8, fields,
+                                1) == -1);
+    CHECK(choose_selector_apply(NULL, bad_utf8, 0, fields, 1) == -1);
+    choose_selector_free(selector);
+    choose_selector_free(NULL);
+}
+
+int main(void) {
+    test_select();
+    test_small_capacity();
+    test_errors();
+    return failures != 0;
+}
@@ -0,0 +1,60 @@
+//! Checks the C interface from C: the committed header matches src/ffi.rs, and a C program
+//! using it links against the library and passes its checks
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn crate_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Set CHOOSE_UPDATE_HEADER, as `make header` does, to rewrite include/choose.h after changing
+/// src/ffi.rs
+#[test]
+fn header_is_up_to_date() {
+    let mut header = Vec::new();
+    cbindgen::generate(crate_dir())
+        .expect("Unable to generate C bindings")
+        .write(&mut header);
+
+    let path = crate_dir().join("include/choose.h");
+    if env::var_os("CHOOSE_UPDATE_HEADER").is_some() {
+        fs::write(&path, &header).unwrap();
+    }
+    assert!(
+        fs::read(&path).unwrap() == header,
+        "include/choose.h is out of date, run `make header`"
+    );
+}
+
+#[test]
+fn c_test_program() {
+    // the C library is built into the same directory as this test
+    let deps = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let program = env::temp_dir().join(format!("choose-ffi-test-{}", std::process::id()));
+    let compiler = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+
+    let status = Command::new(compiler)
+        .args(["-Wall", "-Wextra", "-o"])
+        .arg(&program)
+        .arg(crate_dir().join("test/ffi_test.c"))
+        .arg("-I")
+        .arg(crate_dir().join("include"))
+        .arg("-L")
+        .arg(&deps)
+        .arg("-lchoose")
+        .status()
+        .expect("Unable to run the C compiler");
+    assert!(status.success(), "test/ffi_test.c failed to build");
+
+    let status = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &deps)
+        .env("DYLD_LIBRARY_PATH", &deps)
+        .status()
+        .unwrap();
+    fs::remove_file(&program).unwrap();
+    assert!(status.success(), "test/ffi_test.c failed");
+}